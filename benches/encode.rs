@@ -0,0 +1,105 @@
+//! Compares `write::encode_into` with the encoder it replaced, which buffered the content of every
+//! constructed tag in a temporary vector before copying it into its parent.
+//!
+//! Run with `cargo bench --bench encode`.
+
+extern crate asnom;
+
+use asnom::common::{TagClass, TagStructure};
+use asnom::structure::{StructureTag, PL};
+use asnom::write;
+
+use std::time::{Duration, Instant};
+
+/// The encoder `encode_into` used to be.
+fn encode_buffered(buf: &mut Vec<u8>, tag: StructureTag) {
+    let (class, id) = (tag.class, tag.id);
+    match tag.into_payload() {
+        PL::P(v) => {
+            write::write_type(buf, class, TagStructure::Primitive, id).unwrap();
+            write::write_length(buf, v.len()).unwrap();
+            for byte in v {
+                buf.push(byte);
+            }
+        },
+        PL::C(tags) => {
+            write::write_type(buf, class, TagStructure::Constructed, id).unwrap();
+            let mut tmp: Vec<u8> = Vec::new();
+            for tag in tags {
+                encode_buffered(&mut tmp, tag);
+            }
+            write::write_length(buf, tmp.len()).unwrap();
+            for byte in tmp {
+                buf.push(byte);
+            }
+        },
+    }
+}
+
+fn primitive(class: TagClass, id: u64, v: &[u8]) -> StructureTag {
+    StructureTag { class, id, payload: PL::P(v.to_vec()) }
+}
+
+fn constructed(class: TagClass, id: u64, tags: Vec<StructureTag>) -> StructureTag {
+    StructureTag { class, id, payload: PL::C(tags) }
+}
+
+/// An LDAP SearchResultEntry with `attrs` attributes of `values` values each, whose last
+/// attribute holds a value nested `depth` levels deep, as an extension control might.
+fn search_result_entry(attrs: usize, values: usize, depth: usize) -> StructureTag {
+    use TagClass::*;
+
+    let mut attributes: Vec<StructureTag> = (0..attrs).map(|n| {
+        let vals = (0..values).map(|m| primitive(Universal, 4, format!("value {} of attribute {}", m, n).as_bytes())).collect();
+        constructed(Universal, 16, vec![
+            primitive(Universal, 4, format!("attribute{}", n).as_bytes()),
+            constructed(Universal, 17, vals),
+        ])
+    }).collect();
+
+    let mut nested = primitive(Universal, 4, b"innermost");
+    for n in 0..depth {
+        nested = constructed(Context, n as u64 % 4, vec![primitive(Universal, 2, &[n as u8]), nested]);
+    }
+    attributes.push(constructed(Universal, 16, vec![primitive(Universal, 4, b"nested"), nested]));
+
+    constructed(Universal, 16, vec![
+        primitive(Universal, 2, &[0x01]),
+        constructed(Application, 4, vec![
+            primitive(Universal, 4, b"cn=Someone,ou=People,dc=example,dc=org"),
+            constructed(Universal, 16, attributes),
+        ]),
+    ])
+}
+
+/// Average time of encoding `tag` with `encode`, over enough runs to take about a second.
+fn measure<F: Fn(&mut Vec<u8>, StructureTag)>(tag: &StructureTag, encode: F) -> Duration {
+    let mut runs = 0u32;
+    let mut total = Duration::new(0, 0);
+    while total < Duration::from_secs(1) {
+        let tag = tag.clone();
+        let mut buf = Vec::new();
+        let start = Instant::now();
+        encode(&mut buf, tag);
+        total += start.elapsed();
+        runs += 1;
+    }
+    total / runs
+}
+
+fn main() {
+    for &(attrs, values, depth) in [(10, 5, 10), (10, 5, 100), (10, 5, 1000), (100, 20, 1000)].iter() {
+        let tag = search_result_entry(attrs, values, depth);
+
+        let mut old = Vec::new();
+        encode_buffered(&mut old, tag.clone());
+        let mut new = Vec::new();
+        write::encode_into(&mut new, tag.clone()).unwrap();
+        assert_eq!(old, new);
+
+        let buffered = measure(&tag, encode_buffered);
+        let precomputed = measure(&tag, |buf, tag| write::encode_into(buf, tag).unwrap());
+        println!("{:>3} attributes, {:>2} values, depth {:>4}, {:>7} octets: buffered {:>10?}, precomputed lengths {:>10?}",
+                 attrs, values, depth, new.len(), buffered, precomputed);
+    }
+}
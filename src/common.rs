@@ -33,3 +33,11 @@ impl TagClass {
         }
     }
 }
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Length {
+    /// The content is exactly this many octets long.
+    Definite(u64),
+    /// The content is terminated by end-of-contents octets. Only valid for constructed tags.
+    Indefinite,
+}
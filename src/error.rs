@@ -0,0 +1,178 @@
+use common::{TagClass, TagStructure};
+
+use nom::ErrorKind;
+
+use std::error;
+use std::fmt;
+use std::io;
+
+/// Everything that can go wrong while decoding or encoding.
+///
+/// Errors raised while parsing carry the byte offset into the parsed input at which the problem
+/// was detected.
+#[derive(Debug)]
+pub enum Error {
+    /// The input ends in the middle of an element whose enclosing length says it is complete.
+    Truncated { offset: usize },
+    /// A length that is reserved, too big, or not allowed for this kind of tag.
+    InvalidLength { offset: usize },
+    /// A tag number that does not fit into an u64.
+    TagOverflow { offset: usize },
+    /// End-of-contents octets that are malformed.
+    InvalidEndOfContents { offset: usize },
+    /// Valid BER, but not encoded in the shortest possible form.
+    NonMinimalEncoding { offset: usize },
+    /// Valid BER, but not in the form the distinguished or canonical encoding rules require.
+    NonCanonical { offset: usize },
+    /// Content octets that are not a valid encoding of a value of their type, with the offset
+    /// counted from the start of the content.
+    InvalidContent { offset: usize },
+    /// A value that does not fit into the type it is decoded into.
+    Overflow { offset: usize },
+    /// Constructed tags are nested deeper than allowed.
+    DepthExceeded { offset: usize },
+    /// A tag with more content octets than allowed.
+    ElementTooLarge { offset: usize },
+    /// More tags than allowed.
+    TooManyTags { offset: usize },
+    /// Decoding would take up more memory than allowed.
+    AllocationExceeded { offset: usize },
+    /// A tag with a different class, structure or id than the one required.
+    UnexpectedTag {
+        expected: (TagClass, TagStructure, u64),
+        found: (TagClass, TagStructure, u64),
+    },
+    /// An ENUMERATED value with no matching variant in the Rust enum it is converted into.
+    UnknownDiscriminant { value: i64 },
+    /// Constructed tags of an `IndefiniteWriter` that were not started and ended in pairs, with
+    /// the number of tags that were open.
+    Unbalanced { open: usize },
+    /// Writing the encoded output failed.
+    Io(io::Error),
+}
+
+impl Error {
+    /// The byte offset into the parsed input the error refers to, if it has one.
+    pub fn offset(&self) -> Option<usize> {
+        match *self {
+            Error::Truncated { offset } |
+            Error::InvalidLength { offset } |
+            Error::TagOverflow { offset } |
+            Error::InvalidEndOfContents { offset } |
+            Error::NonMinimalEncoding { offset } |
+            Error::NonCanonical { offset } |
+            Error::InvalidContent { offset } |
+            Error::Overflow { offset } |
+            Error::DepthExceeded { offset } |
+            Error::ElementTooLarge { offset } |
+            Error::TooManyTags { offset } |
+            Error::AllocationExceeded { offset } => Some(offset),
+            Error::UnexpectedTag { .. } |
+            Error::UnknownDiscriminant { .. } |
+            Error::Unbalanced { .. } |
+            Error::Io(_) => None,
+        }
+    }
+
+    /// Move the offset of the error further into the input, for errors found in nested content.
+    pub(crate) fn shift(self, by: usize) -> Error {
+        match self {
+            Error::Truncated { offset } => Error::Truncated { offset: offset + by },
+            Error::InvalidLength { offset } => Error::InvalidLength { offset: offset + by },
+            Error::TagOverflow { offset } => Error::TagOverflow { offset: offset + by },
+            Error::InvalidEndOfContents { offset } => Error::InvalidEndOfContents { offset: offset + by },
+            Error::NonMinimalEncoding { offset } => Error::NonMinimalEncoding { offset: offset + by },
+            Error::NonCanonical { offset } => Error::NonCanonical { offset: offset + by },
+            Error::InvalidContent { offset } => Error::InvalidContent { offset: offset + by },
+            Error::Overflow { offset } => Error::Overflow { offset: offset + by },
+            Error::DepthExceeded { offset } => Error::DepthExceeded { offset: offset + by },
+            Error::ElementTooLarge { offset } => Error::ElementTooLarge { offset: offset + by },
+            Error::TooManyTags { offset } => Error::TooManyTags { offset: offset + by },
+            Error::AllocationExceeded { offset } => Error::AllocationExceeded { offset: offset + by },
+            e => e,
+        }
+    }
+}
+
+impl PartialEq for Error {
+    fn eq(&self, other: &Error) -> bool {
+        match (self, other) {
+            // io::Error can't be compared, so the kind has to do.
+            (Error::Io(a), Error::Io(b)) => a.kind() == b.kind(),
+            (Error::UnexpectedTag { expected: a, found: b },
+             Error::UnexpectedTag { expected: c, found: d }) => a == c && b == d,
+            (Error::UnknownDiscriminant { value: a }, Error::UnknownDiscriminant { value: b }) => a == b,
+            (Error::Unbalanced { open: a }, Error::Unbalanced { open: b }) => a == b,
+            (a, b) => a.offset().is_some()
+                && a.offset() == b.offset()
+                && ::std::mem::discriminant(a) == ::std::mem::discriminant(b),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Truncated { offset } =>
+                write!(f, "element truncated at offset {}", offset),
+            Error::InvalidLength { offset } =>
+                write!(f, "invalid length at offset {}", offset),
+            Error::TagOverflow { offset } =>
+                write!(f, "tag number overflows u64 at offset {}", offset),
+            Error::InvalidEndOfContents { offset } =>
+                write!(f, "invalid end-of-contents octets at offset {}", offset),
+            Error::NonMinimalEncoding { offset } =>
+                write!(f, "non-minimal encoding at offset {}", offset),
+            Error::NonCanonical { offset } =>
+                write!(f, "non-canonical encoding at offset {}", offset),
+            Error::InvalidContent { offset } =>
+                write!(f, "invalid content at offset {}", offset),
+            Error::Overflow { offset } =>
+                write!(f, "value too large at offset {}", offset),
+            Error::DepthExceeded { offset } =>
+                write!(f, "maximum nesting depth exceeded at offset {}", offset),
+            Error::ElementTooLarge { offset } =>
+                write!(f, "element too large at offset {}", offset),
+            Error::TooManyTags { offset } =>
+                write!(f, "too many tags at offset {}", offset),
+            Error::AllocationExceeded { offset } =>
+                write!(f, "allocation limit exceeded at offset {}", offset),
+            Error::UnexpectedTag { expected, found } =>
+                write!(f, "expected tag {:?}, found {:?}", expected, found),
+            Error::UnknownDiscriminant { value } =>
+                write!(f, "unknown enumerated value {}", value),
+            Error::Unbalanced { open: 0 } =>
+                write!(f, "no constructed tag open to end"),
+            Error::Unbalanced { open } =>
+                write!(f, "{} constructed tags left open", open),
+            Error::Io(ref e) =>
+                write!(f, "I/O error: {}", e),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            Error::Io(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Every parser in this crate reports its errors as `Custom`. Should nom report one of its own,
+/// the offset is not known.
+impl From<ErrorKind<Error>> for Error {
+    fn from(e: ErrorKind<Error>) -> Error {
+        match e {
+            ErrorKind::Custom(e) => e,
+            _ => Error::InvalidContent { offset: 0 },
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Error {
+        Error::Io(e)
+    }
+}
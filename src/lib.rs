@@ -7,9 +7,13 @@ pub mod write;
 
 
 pub mod common;
+pub mod error;
 pub mod universal;
 pub mod structures;
 pub mod structure;
+pub mod reader;
+pub mod traits;
+pub mod specific;
 
 pub use nom::IResult;
 pub use nom::IResult::*;
@@ -19,3 +23,4 @@ pub use nom::ConsumerState;
 pub use nom::Input;
 pub use nom::Move;
 pub use parse::Parser;
+pub use error::Error;
@@ -1,146 +1,690 @@
-use structure::{StructureTag, PL};
+use structure::{StructureTag, StructureTagRef, PL, PLRef};
 use common::TagStructure;
 use common::TagClass;
+use common::Length;
+use error::Error;
+use universal;
+use structures::{time, real};
+use write::{encode_to_vec, encoded_len, type_len, length_len, redundant_int_octets,
+            SetOrder, SetMember, CER_SEGMENT_LEN};
 
-use nom;
-use nom::InputLength;
 use nom::IResult;
+use nom::ErrorKind;
+use nom::Needed;
 use nom::Consumer;
 use nom::ConsumerState;
-use nom::ConsumerState::*;
 use nom::Input;
 use nom::Input::*;
 use nom::Move;
 
-named!(class_bits<(&[u8], usize), TagClass>,
-    map_opt!(
-        take_bits!(u8, 2),
-        TagClass::from_u8
-    )
-);
-
-named!(pc_bit<(&[u8], usize), TagStructure>,
-    map_opt!(
-        take_bits!(u8, 1),
-        TagStructure::from_u8
-    )
-);
-
-named!(tagnr_bits<(&[u8], usize), u64>,
-    take_bits!(u64, 5)
-);
-
-named!(pub parse_type_header<(TagClass, TagStructure, u64)>, bits!(
-    do_parse!(
-        class: class_bits >>
-        pc: pc_bit >>
-        tagnr: tagnr_bits >>
-        ((class, pc, tagnr))
-   )
-));
-
-named!(pub parse_length<u64>,
-    alt!(
-        bits!(
-            do_parse!(
-                // Short length form
-                tag_bits!(u8, 1, 0u8) >>
-                len: take_bits!(u64, 7) >>
-                (len)
-            )
-        )
-    |
-        length_value!(
-            bits!(
-                do_parse!(
-                    /* // TODO: Fix nom to be able to do this.
-                     *return_error!(nom::ErrorKind::Custom(1),
-                     *    not!(tag_bits!(u8, 8, 255u8))
-                     *) >>
-                     */
-                    // Long length form
-                    tag_bits!(u8, 1, 1u8) >>
-                    len: take_bits!(u8, 7) >>
-                    (len)
-                )
-            ),
-            parse_uint
-        )
-    )
-);
-
-
-
-pub fn parse_uint(i: &[u8]) -> nom::IResult<&[u8], u64> {
-    match i.len() {
-        1 => nom::be_u8(i).map(|x| x as u64),
-        2 => nom::be_u16(i).map(|x| x as u64),
-        3 => nom::be_u32(i).map(|x| x as u64),
-        8 => nom::be_u64(i),
-        _ => unimplemented!()
-    }
-}
-
-pub fn parse_tag(i: &[u8]) -> nom::IResult<&[u8], StructureTag> {
-    let (mut i, ((class, structure, id),len)) = try_parse!(i, do_parse!(
-        hdr: parse_type_header >>
-        len: parse_length >>
-        ((hdr, len))
-    ));
-
-    let pl: PL = match structure {
-        TagStructure::Primitive => {
-            let (j, content) = try_parse!(i, length_data!(value!(len)));
-            i = j;
-
-            PL::P(content.to_vec())
+use byteorder::{BigEndian, ByteOrder};
+
+use std::convert::TryFrom;
+
+pub(crate) fn fail<I, O>(e: Error) -> IResult<I, O, Error> {
+    IResult::Error(ErrorKind::Custom(e))
+}
+
+/// Run `f` on input that starts `pos` bytes into the input of the caller, adjusting error offsets
+/// and needed sizes to be relative to the caller's input.
+pub(crate) fn offset_by<'a, O, F>(i: &'a [u8], pos: usize, f: F) -> IResult<&'a [u8], O, Error>
+    where F: FnOnce(&'a [u8]) -> IResult<&'a [u8], O, Error>
+{
+    match f(i) {
+        IResult::Error(ErrorKind::Custom(e)) => fail(e.shift(pos)),
+        IResult::Incomplete(Needed::Size(n)) => IResult::Incomplete(Needed::Size(pos.saturating_add(n))),
+        r => r,
+    }
+}
+
+/// Parse the identifier octets of a tag.
+///
+/// Tag numbers above 30 are read from the subsequent base-128 octets. Leading zero octets in that
+/// form are accepted; use `parse_type_header_strict` to reject them.
+pub fn parse_type_header(i: &[u8]) -> IResult<&[u8], (TagClass, TagStructure, u64), Error> {
+    type_header(i, false)
+}
+
+/// Parse the identifier octets of a tag, rejecting tag numbers that are not minimally encoded.
+pub fn parse_type_header_strict(i: &[u8]) -> IResult<&[u8], (TagClass, TagStructure, u64), Error> {
+    type_header(i, true)
+}
+
+fn type_header(i: &[u8], strict: bool) -> IResult<&[u8], (TagClass, TagStructure, u64), Error> {
+    let byte = match i.first() {
+        Some(&byte) => byte,
+        None => return IResult::Incomplete(Needed::Size(1)),
+    };
+
+    // First two bits: Class, Bit 6: Primitive/Constructed, Bit 5-1: Tag Number
+    let class = match byte >> 6 {
+        0 => TagClass::Universal,
+        1 => TagClass::Application,
+        2 => TagClass::Context,
+        _ => TagClass::Private,
+    };
+    let pc = if byte & 0x20 == 0 { TagStructure::Primitive } else { TagStructure::Constructed };
+    let tagnr = (byte & 0x1F) as u64;
+
+    // All five tag bits set means the actual number follows in the extended form.
+    if tagnr != 0x1F {
+        return IResult::Done(&i[1..], (class, pc, tagnr));
+    }
+
+    let (j, id) = try_parse!(&i[1..], apply!(offset_by, 1, |j| parse_tag_number(j, strict)));
+    IResult::Done(j, (class, pc, id))
+}
+
+fn parse_tag_number(i: &[u8], strict: bool) -> IResult<&[u8], u64, Error> {
+    let mut id: u64 = 0;
+
+    for (n, byte) in i.iter().enumerate() {
+        if strict && n == 0 && *byte == 0x80 {
+            return fail(Error::NonMinimalEncoding { offset: n });
+        }
+        if id > (u64::MAX >> 7) {
+            return fail(Error::TagOverflow { offset: n });
+        }
+
+        id = (id << 7) | (byte & 0x7F) as u64;
+
+        // The last octet of the tag number has the high bit cleared.
+        if byte & 0x80 == 0 {
+            if strict && id < 0x1F {
+                return fail(Error::NonMinimalEncoding { offset: n });
+            }
+            return IResult::Done(&i[n+1..], id);
         }
-        TagStructure::Constructed => {
-            let (j, mut content) = try_parse!(i, length_bytes!(value!(len)));
-            i = j;
-
-            let mut tv: Vec<StructureTag> = Vec::new();
-            while content.input_len() > 0 {
-                let pres = try_parse!(content, call!(parse_tag));
-                content = pres.0;
-                let res: StructureTag = pres.1;
-                tv.push(res);
+    }
+
+    IResult::Incomplete(Needed::Size(i.len() + 1))
+}
+
+/// Parse the length octets of a tag.
+pub fn parse_length(i: &[u8]) -> IResult<&[u8], Length, Error> {
+    length(i, false)
+}
+
+/// Parse the length octets of a tag, rejecting long form lengths that are not minimally encoded.
+pub fn parse_length_strict(i: &[u8]) -> IResult<&[u8], Length, Error> {
+    length(i, true)
+}
+
+fn length(i: &[u8], strict: bool) -> IResult<&[u8], Length, Error> {
+    match i.first() {
+        None => IResult::Incomplete(Needed::Size(1)),
+        Some(&0x80) => IResult::Done(&i[1..], Length::Indefinite),
+        // Short length form
+        Some(&len) if len & 0x80 == 0 => IResult::Done(&i[1..], Length::Definite(len as u64)),
+        // Long length form
+        Some(&len) => {
+            let count = (len & 0x7F) as usize;
+
+            // A first length octet of 0xFF is reserved for future extensions.
+            if count == 0x7F || count > 8 {
+                return fail(Error::InvalidLength { offset: 0 });
+            }
+            if i.len() < count + 1 {
+                return IResult::Incomplete(Needed::Size(count + 1));
+            }
+
+            let (_, len) = try_parse!(&i[1..count+1], apply!(offset_by, 1, parse_uint));
+            if usize::try_from(len).is_err() {
+                return fail(Error::InvalidLength { offset: 0 });
+            }
+            if strict && i[1] == 0 {
+                return fail(Error::NonMinimalEncoding { offset: 1 });
+            }
+            if strict && len < 128 {
+                return fail(Error::NonMinimalEncoding { offset: 0 });
             }
 
-            PL::C(tv)
+            IResult::Done(&i[count+1..], Length::Definite(len))
         }
-    };
+    }
+}
+
+/// Read the whole input as a big-endian unsigned integer of one to eight octets.
+pub fn parse_uint(i: &[u8]) -> IResult<&[u8], u64, Error> {
+    if i.is_empty() || i.len() > 8 {
+        return fail(Error::InvalidLength { offset: 0 });
+    }
+
+    IResult::Done(&i[i.len()..], BigEndian::read_uint(i, i.len()))
+}
+
+/// The set of encoding rules the parser holds its input to.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum Rules {
+    Ber,
+    Der,
+    Cer,
+}
+
+/// Limits on the input a parser accepts, to bound the resources untrusted input can make it use.
+///
+/// `parse_tag` and its variants use the default limits; `parse_tag_with_config`,
+/// `parse_tag_ref_with_config` and `Parser::with_config` take others.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ParserConfig {
+    /// How many constructed tags may be nested inside each other. The default is 128.
+    ///
+    /// Parsing, encoding and dropping a `StructureTag` don't recurse, but cloning, comparing or
+    /// formatting one does, so a tag nested deeper than the stack allows for has to be kept away
+    /// from those.
+    pub max_depth: usize,
+    /// How many content octets a single tag may have. Definite lengths above this are rejected
+    /// before waiting for the content. The default is 16 MiB.
+    pub max_element_size: usize,
+    /// How many tags, nested ones included, a single top-level tag may consist of. The default is
+    /// 2^20.
+    pub max_tags: usize,
+    /// How many octets the decoded tag may take up, counting the tags themselves and, as a
+    /// `StructureTag` copies it, the content of primitive tags. The default is 64 MiB.
+    pub max_allocation: usize,
+}
+
+impl ParserConfig {
+    /// A configuration that accepts everything, for trusted input.
+    pub fn unlimited() -> ParserConfig {
+        ParserConfig {
+            max_depth: usize::MAX,
+            max_element_size: usize::MAX,
+            max_tags: usize::MAX,
+            max_allocation: usize::MAX,
+        }
+    }
+}
+
+impl Default for ParserConfig {
+    fn default() -> ParserConfig {
+        ParserConfig {
+            max_depth: 128,
+            max_element_size: 16 << 20,
+            max_tags: 1 << 20,
+            max_allocation: 64 << 20,
+        }
+    }
+}
+
+/// What the tag being parsed has used up of the limits of a `ParserConfig`.
+struct Limits<'c> {
+    config: &'c ParserConfig,
+    tags: usize,
+    allocated: usize,
+}
+
+impl<'c> Limits<'c> {
+    fn new(config: &'c ParserConfig) -> Limits<'c> {
+        Limits { config, tags: 0, allocated: 0 }
+    }
+
+    /// Account for a tag of `len` content octets that takes up `size` octets once decoded.
+    fn add_tag(&mut self, len: Option<usize>, size: usize) -> Result<(), Error> {
+        self.tags += 1;
+        if self.tags > self.config.max_tags {
+            return Err(Error::TooManyTags { offset: 0 });
+        }
+        if matches!(len, Some(len) if len > self.config.max_element_size) {
+            return Err(Error::ElementTooLarge { offset: 0 });
+        }
+
+        self.allocated = self.allocated.saturating_add(size);
+        if self.allocated > self.config.max_allocation {
+            return Err(Error::AllocationExceeded { offset: 0 });
+        }
+
+        Ok(())
+    }
+}
+
+pub fn parse_tag(i: &[u8]) -> IResult<&[u8], StructureTag, Error> {
+    parse_tag_with_config(i, &ParserConfig::default())
+}
+
+/// Parse a tag like `parse_tag`, holding the input to the limits of `config` instead of the
+/// default ones.
+pub fn parse_tag_with_config<'a>(i: &'a [u8], config: &ParserConfig) -> IResult<&'a [u8], StructureTag, Error> {
+    parse_tag_with(i, Rules::Ber, &mut Limits::new(config))
+}
+
+/// Parse a tag like `parse_tag`, but borrow the content of primitive tags from the input instead
+/// of copying it.
+pub fn parse_tag_ref(i: &[u8]) -> IResult<&[u8], StructureTagRef<'_>, Error> {
+    parse_tag_ref_with_config(i, &ParserConfig::default())
+}
 
-    nom::IResult::Done(i, StructureTag {
-        class: class,
-        id: id,
-        payload: pl,
-    })
+/// Parse a tag like `parse_tag_ref`, holding the input to the limits of `config` instead of the
+/// default ones.
+pub fn parse_tag_ref_with_config<'a>(i: &'a [u8], config: &ParserConfig) -> IResult<&'a [u8], StructureTagRef<'a>, Error> {
+    parse_tag_with(i, Rules::Ber, &mut Limits::new(config))
+}
+
+/// Parse a tag encoded with the Distinguished Encoding Rules, rejecting any other encoding.
+///
+/// On top of what `parse_tag` checks, tag numbers and lengths have to be minimally encoded, the
+/// indefinite length form must not be used and the content has to pass `validate_der`, which
+/// accepts the members of a SET in either SET or SET OF order.
+pub fn parse_tag_der(i: &[u8]) -> IResult<&[u8], StructureTag, Error> {
+    let (j, tag): (_, StructureTag) =
+        try_parse!(i, apply!(parse_tag_with, Rules::Der, &mut Limits::new(&ParserConfig::default())));
+
+    // With the framing known to be DER, offsets into the DER encoding are offsets into the input.
+    if let Err(e) = validate_der(&tag) {
+        return fail(e);
+    }
+
+    IResult::Done(j, tag)
+}
+
+/// Parse a tag encoded with the Canonical Encoding Rules, rejecting any other encoding.
+///
+/// On top of what `parse_tag` checks, tag numbers and lengths have to be minimally encoded,
+/// constructed tags must use the indefinite length form and the content has to pass
+/// `validate_cer`.
+pub fn parse_tag_cer(i: &[u8]) -> IResult<&[u8], StructureTag, Error> {
+    let (j, tag): (_, StructureTag) =
+        try_parse!(i, apply!(parse_tag_with, Rules::Cer, &mut Limits::new(&ParserConfig::default())));
+
+    // With the framing known to be CER, offsets into the CER encoding are offsets into the input.
+    if let Err(e) = validate_cer(&tag) {
+        return fail(e);
+    }
+
+    IResult::Done(j, tag)
+}
+
+/// Class, structure, id and length of a tag, and the number of its identifier octets.
+type Header = (TagClass, TagStructure, u64, Length, usize);
+
+/// Parse the identifier and length octets of a tag.
+fn tag_header(i: &[u8], strict: bool) -> IResult<&[u8], Header, Error> {
+    let (j, (class, structure, id)) = try_parse!(i, apply!(type_header, strict));
+    let type_len = i.len() - j.len();
+    let (j, len) = try_parse!(j, apply!(offset_by, type_len, |j| length(j, strict)));
+    IResult::Done(j, (class, structure, id, len, type_len))
+}
+
+/// A tag `parse_tag_with` can build: a `StructureTag`, which copies the content of primitive
+/// tags, or a `StructureTagRef`, which borrows it from the input.
+trait ParsedTag<'a>: Sized {
+    /// Number of octets a tag with `len` content octets takes up, as counted against
+    /// `ParserConfig::max_allocation`.
+    fn size(structure: TagStructure, len: usize) -> usize;
+    fn primitive(class: TagClass, id: u64, content: &'a [u8]) -> Self;
+    fn constructed(class: TagClass, id: u64, children: Vec<Self>) -> Self;
+}
+
+impl<'a> ParsedTag<'a> for StructureTag {
+    fn size(structure: TagStructure, len: usize) -> usize {
+        let copied = if structure == TagStructure::Primitive { len } else { 0 };
+        ::std::mem::size_of::<StructureTag>().saturating_add(copied)
+    }
+
+    fn primitive(class: TagClass, id: u64, content: &'a [u8]) -> StructureTag {
+        StructureTag { class, id, payload: PL::P(content.to_vec()) }
+    }
+
+    fn constructed(class: TagClass, id: u64, children: Vec<StructureTag>) -> StructureTag {
+        StructureTag { class, id, payload: PL::C(children) }
+    }
+}
+
+impl<'a> ParsedTag<'a> for StructureTagRef<'a> {
+    fn size(_: TagStructure, _: usize) -> usize {
+        ::std::mem::size_of::<StructureTagRef>()
+    }
+
+    fn primitive(class: TagClass, id: u64, content: &'a [u8]) -> StructureTagRef<'a> {
+        StructureTagRef { class, id, payload: PLRef::P(content) }
+    }
+
+    fn constructed(class: TagClass, id: u64, children: Vec<StructureTagRef<'a>>) -> StructureTagRef<'a> {
+        StructureTagRef { class, id, payload: PLRef::C(children) }
+    }
+}
+
+/// A constructed tag whose content `parse_tag_with` is in the middle of.
+struct Frame<T> {
+    class: TagClass,
+    id: u64,
+    /// Offset of the tag in the input.
+    start: usize,
+    /// Offset of the content in the input.
+    content_start: usize,
+    /// Offset of the end of the content, unless it has indefinite length.
+    end: Option<usize>,
+    /// How far the content may extend: to its own end, or to that of the innermost enclosing tag
+    /// of definite length.
+    bound: usize,
+    /// Where to report the input as truncated if it ends inside indefinite length content, or
+    /// `None` to report it as incomplete. That is the outermost tag of indefinite length inside
+    /// the innermost tag of definite length, whose content is known to be complete.
+    truncated: Option<usize>,
+    children: Vec<T>,
+}
+
+/// Parse a tag, keeping the constructed tags it is inside of on a stack instead of recursing, so
+/// that deep nesting can't overflow the call stack. Offsets are counted from the start of `i`.
+fn parse_tag_with<'a, T: ParsedTag<'a>>(i: &'a [u8], rules: Rules, limits: &mut Limits) -> IResult<&'a [u8], T, Error> {
+    let strict = rules != Rules::Ber;
+    let mut stack: Vec<Frame<T>> = Vec::new();
+    let mut pos = 0;
+
+    loop {
+        let (bound, truncated) = match stack.last() {
+            Some(frame) if frame.end.is_some() => (frame.bound, Some(pos)),
+            Some(frame) => (frame.bound, frame.truncated),
+            None => (i.len(), None),
+        };
+        let incomplete = |needed| match truncated {
+            Some(offset) => fail(Error::Truncated { offset }),
+            None => IResult::Incomplete(needed),
+        };
+
+        // Close the innermost constructed tag if its content ends here.
+        let closed = match stack.last() {
+            Some(&Frame { end: Some(end), .. }) => pos == end,
+            // End-of-contents is a primitive universal tag 0 of length 0, i.e. two zero octets.
+            Some(&Frame { end: None, .. }) => match (i[..bound].get(pos), i[..bound].get(pos + 1)) {
+                (Some(&0), Some(&0)) => true,
+                (Some(&0), Some(_)) => return fail(Error::InvalidEndOfContents { offset: pos }),
+                (Some(&0), None) => return incomplete(Needed::Size(pos + 2)),
+                _ => false,
+            },
+            None => false,
+        };
+        if closed {
+            let frame = stack.pop().unwrap();
+            if frame.end.is_none() {
+                // The length of the content is only known once its end is found.
+                if pos - frame.content_start > limits.config.max_element_size {
+                    return fail(Error::ElementTooLarge { offset: frame.start });
+                }
+                pos += 2;
+            }
+
+            let tag = T::constructed(frame.class, frame.id, frame.children);
+            match stack.last_mut() {
+                Some(parent) => parent.children.push(tag),
+                None => return IResult::Done(&i[pos..], tag),
+            }
+            continue;
+        }
+
+        let (j, (class, structure, id, len, type_len)) =
+            match offset_by(&i[pos..bound], pos, |j| tag_header(j, strict)) {
+                IResult::Done(j, header) => (j, header),
+                IResult::Incomplete(needed) => return incomplete(needed),
+                IResult::Error(e) => return IResult::Error(e),
+            };
+        let content_start = bound - j.len();
+
+        if structure == TagStructure::Constructed {
+            match (rules, len) {
+                (Rules::Der, Length::Indefinite) | (Rules::Cer, Length::Definite(_)) =>
+                    return fail(Error::NonCanonical { offset: pos + type_len }),
+                _ => (),
+            }
+        }
+
+        let content_len = match len {
+            Length::Definite(len) => Some(len as usize),
+            Length::Indefinite => None,
+        };
+        if let Err(e) = limits.add_tag(content_len, T::size(structure, content_len.unwrap_or(0))) {
+            return fail(e.shift(pos));
+        }
+        if structure == TagStructure::Constructed && stack.len() >= limits.config.max_depth {
+            return fail(Error::DepthExceeded { offset: pos });
+        }
+
+        match (structure, content_len) {
+            (TagStructure::Primitive, Some(len)) => {
+                if j.len() < len {
+                    return incomplete(Needed::Size(content_start.saturating_add(len)));
+                }
+
+                let tag = T::primitive(class, id, &j[..len]);
+                pos = content_start + len;
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(tag),
+                    None => return IResult::Done(&i[pos..], tag),
+                }
+            }
+            (TagStructure::Primitive, None) => {
+                return fail(Error::InvalidLength { offset: content_start - 1 });
+            }
+            (TagStructure::Constructed, Some(len)) => {
+                if j.len() < len {
+                    return incomplete(Needed::Size(content_start.saturating_add(len)));
+                }
+
+                let end = content_start + len;
+                stack.push(Frame {
+                    class, id, start: pos, content_start, end: Some(end), bound: end, truncated,
+                    children: Vec::new(),
+                });
+                pos = content_start;
+            }
+            (TagStructure::Constructed, None) => {
+                stack.push(Frame {
+                    class, id, start: pos, content_start, end: None, bound, truncated,
+                    children: Vec::new(),
+                });
+                pos = content_start;
+            }
+        }
+    }
+}
+
+/// Check that a tag is in the form the Distinguished Encoding Rules require.
+///
+/// This covers the rules `write::encode_der_into` enforces for universal tags: booleans must be
+/// 0x00 or 0xFF, integers and enumerations minimal, string types primitive and the members of a
+/// SET sorted. The offset in the returned error refers to the DER encoding of `tag`, which is the
+/// input it was parsed from if that was DER up to the offending element.
+///
+/// **The SET order is only checked loosely.** SET and SET OF share the same tag, so without the
+/// schema there is no telling which of the two a universal tag 17 holds. Its members are accepted
+/// if they are sorted by tag, as DER requires for a SET, or by their encodings, as it requires for
+/// a SET OF. A SET whose members happen to be in SET OF order, or the other way round, passes even
+/// though it is not valid DER; check the order against the schema if that matters.
+pub fn validate_der(tag: &StructureTag) -> Result<(), Error> {
+    check_der(tag).map(|_| ())
+}
+
+/// Validate `tag` and return the length of its encoding.
+fn check_der(tag: &StructureTag) -> Result<usize, Error> {
+    match tag.payload {
+        PL::P(ref v) => {
+            let hdr_len = type_len(tag.id) + length_len(v.len());
+            check_canonical_content(tag.class, tag.id, v).map_err(|e| e.shift(hdr_len))?;
+            Ok(hdr_len + v.len())
+        },
+        PL::C(ref tags) => {
+            let universal = tag.class == TagClass::Universal;
+            if universal && universal::is_string_type(tag.id) {
+                return Err(Error::NonCanonical { offset: 0 });
+            }
+            let is_set = universal && tag.id == universal::Types::Set as u64;
+
+            let mut pos = 0;
+            let mut order = SetOrder::new();
+            let mut prev: Option<SetMember> = None;
+            for (n, child) in tags.iter().enumerate() {
+                let res = check_der(child).and_then(|len| {
+                    if is_set {
+                        let member = (child.class, child.id, encode_to_vec(child, Rules::Ber));
+                        if matches!(prev, Some(ref p) if !order.follows(p, &member)) {
+                            return Err(Error::NonCanonical { offset: 0 });
+                        }
+                        prev = Some(member);
+                    }
+                    Ok(len)
+                });
+
+                match res {
+                    Ok(len) => pos += len,
+                    Err(e) => {
+                        let content_len = pos + tags[n..].iter().map(encoded_len).sum::<usize>();
+                        return Err(e.shift(type_len(tag.id) + length_len(content_len) + pos));
+                    }
+                }
+            }
+
+            Ok(type_len(tag.id) + length_len(pos) + pos)
+        }
+    }
+}
+
+/// Check that a tag is in the form the Canonical Encoding Rules require.
+///
+/// The rules on booleans, integers and SET ordering are the same as for `validate_der`, which
+/// includes accepting either SET or SET OF order. String values longer than 1000 octets have to be
+/// split into a constructed string of 1000 octet segments, and shorter ones be primitive. The
+/// offset in the returned error refers to the CER encoding of `tag`.
+pub fn validate_cer(tag: &StructureTag) -> Result<(), Error> {
+    check_cer(tag).map(|_| ())
+}
+
+/// Validate `tag` and return the length of its encoding.
+fn check_cer(tag: &StructureTag) -> Result<usize, Error> {
+    let universal = tag.class == TagClass::Universal;
+    let string = universal && universal::is_string_type(tag.id);
+
+    match tag.payload {
+        PL::P(ref v) => {
+            if string && v.len() > CER_SEGMENT_LEN {
+                return Err(Error::NonCanonical { offset: 0 });
+            }
+
+            let hdr_len = type_len(tag.id) + length_len(v.len());
+            check_canonical_content(tag.class, tag.id, v).map_err(|e| e.shift(hdr_len))?;
+            Ok(hdr_len + v.len())
+        },
+        PL::C(ref tags) => {
+            // Constructed tags are always followed by the single octet of the indefinite length.
+            let hdr_len = type_len(tag.id) + 1;
+            let is_set = universal && tag.id == universal::Types::Set as u64;
+            let bitstring = tag.id == universal::Types::BitString as u64;
+
+            let mut pos = hdr_len;
+            let mut total = 0;
+            let mut order = SetOrder::new();
+            let mut prev: Option<SetMember> = None;
+            for (n, child) in tags.iter().enumerate() {
+                if string {
+                    let seg_id = if bitstring { universal::Types::BitString } else { universal::Types::OctetString };
+                    let seg = match child.payload {
+                        PL::P(ref seg) if child.class == TagClass::Universal && child.id == seg_id as u64 => seg,
+                        _ => return Err(Error::NonCanonical { offset: pos }),
+                    };
+                    let last = n + 1 == tags.len();
+                    if (!last && seg.len() != CER_SEGMENT_LEN) || seg.is_empty() || seg.len() > CER_SEGMENT_LEN {
+                        return Err(Error::NonCanonical { offset: pos });
+                    }
+                    if bitstring && !last && seg[0] != 0 {
+                        return Err(Error::NonCanonical { offset: pos + type_len(child.id) + length_len(seg.len()) });
+                    }
+                    total += seg.len();
+                }
+
+                let len = check_cer(child).map_err(|e| e.shift(pos))?;
+                if is_set {
+                    let member = (child.class, child.id, encode_to_vec(child, Rules::Cer));
+                    if matches!(prev, Some(ref p) if !order.follows(p, &member)) {
+                        return Err(Error::NonCanonical { offset: pos });
+                    }
+                    prev = Some(member);
+                }
+                pos += len;
+            }
+
+            // Anything that fits into a single segment has to be primitive.
+            if string && total <= CER_SEGMENT_LEN {
+                return Err(Error::NonCanonical { offset: 0 });
+            }
+
+            Ok(pos + 2)
+        }
+    }
+}
+
+/// Check the content octets of a primitive tag against the rules DER and CER share for its type.
+fn check_canonical_content(class: TagClass, id: u64, v: &[u8]) -> Result<(), Error> {
+    if class != TagClass::Universal {
+        return Ok(());
+    }
+
+    if id == universal::Types::Boolean as u64 && v != [0x00] && v != [0xFF] {
+        return Err(Error::NonCanonical { offset: 0 });
+    }
+    let integer = id == universal::Types::Integer as u64 || id == universal::Types::Enumerated as u64;
+    if integer && v.is_empty() {
+        return Err(Error::InvalidContent { offset: 0 });
+    }
+    if id == universal::Types::Null as u64 && !v.is_empty() {
+        return Err(Error::InvalidContent { offset: 0 });
+    }
+    if integer && redundant_int_octets(v) > 0 {
+        return Err(Error::NonMinimalEncoding { offset: 0 });
+    }
+    if id == universal::Types::UtcTime as u64 || id == universal::Types::GeneralizedTime as u64 {
+        time::check_canonical(id, v)?;
+    }
+    if id == universal::Types::Real as u64 {
+        real::check_canonical(v)?;
+    }
+    // The padding bits of a BIT STRING have to be zero.
+    if id == universal::Types::BitString as u64 {
+        if let Some((&unused, bits)) = v.split_first() {
+            if let Some(&last) = bits.last() {
+                if unused < 8 && last & !(0xFF << unused) != 0 {
+                    return Err(Error::NonCanonical { offset: v.len() - 1 });
+                }
+            }
+        }
+    }
+
+    Ok(())
 }
 
 pub struct Parser {
-    state: ConsumerState<StructureTag, (), Move>,
+    state: ConsumerState<StructureTag, Error, Move>,
+    config: ParserConfig,
 }
 
 impl Parser {
     pub fn new() -> Parser {
-        Parser { state: Continue(Move::Consume(0)) }
+        Parser::with_config(ParserConfig::default())
+    }
+
+    /// A parser that holds its input to the limits of `config`.
+    pub fn with_config(config: ParserConfig) -> Parser {
+        Parser { state: ConsumerState::Continue(Move::Consume(0)), config }
+    }
+}
+
+impl Default for Parser {
+    fn default() -> Parser {
+        Parser::new()
     }
 }
 
-impl<'a> Consumer<&'a [u8], StructureTag, (), Move> for Parser {
-    fn handle(&mut self, input: Input<&[u8]>) -> &ConsumerState<StructureTag, (), Move> {
+impl Consumer<&[u8], StructureTag, Error, Move> for Parser {
+    fn handle(&mut self, input: Input<&[u8]>) -> &ConsumerState<StructureTag, Error, Move> {
         use nom::Offset;
         match input {
             Empty | Eof(None) => self.state(),
             Element(data) | Eof(Some(data)) => {
-                self.state = match parse_tag(data) {
+                self.state = match parse_tag_with_config(data, &self.config) {
                     IResult::Incomplete(n) => {
-                        Continue(Move::Await(n))
+                        ConsumerState::Continue(Move::Await(n))
                     },
-                    IResult::Error(_) => Error(()),
+                    IResult::Error(e) => ConsumerState::Error(e.into()),
                     IResult::Done(i, o) => {
-                        Done(Move::Consume(data.offset(i)), o)
+                        ConsumerState::Done(Move::Consume(data.offset(i)), o)
                     }
                 };
 
@@ -150,7 +694,7 @@ impl<'a> Consumer<&'a [u8], StructureTag, (), Move> for Parser {
 
     }
 
-    fn state(&self) -> &ConsumerState<StructureTag, (), Move> {
+    fn state(&self) -> &ConsumerState<StructureTag, Error, Move> {
         &self.state
     }
 }
@@ -159,8 +703,394 @@ impl<'a> Consumer<&'a [u8], StructureTag, (), Move> for Parser {
 mod test {
     use super::*;
     use nom::IResult;
-    use common::{TagClass, TagStructure};
+    use common::{TagClass, TagStructure, Length};
     use structure::{StructureTag, PL};
+    use write::write_type;
+    use error::Error;
+    use nom::{ErrorKind, Needed};
+    use structures::{FromStructure, OctetString};
+    use write::IndefiniteWriter;
+
+    use std::thread;
+
+    #[test]
+    fn test_high_tag_roundtrip() {
+        let ids = [0u64, 30, 31, 127, 128, 16383, 16384, 0xDEAD_BEEF, u64::MAX];
+        for &id in ids.iter() {
+            let mut buf: Vec<u8> = Vec::new();
+            write_type(&mut buf, TagClass::Context, TagStructure::Constructed, id).unwrap();
+            buf.push(0xAA);
+
+            let expected = (TagClass::Context, TagStructure::Constructed, id);
+            assert_eq!(parse_type_header(&buf[..]), IResult::Done(&[0xAA][..], expected));
+            assert_eq!(parse_type_header_strict(&buf[..]), IResult::Done(&[0xAA][..], expected));
+        }
+    }
+
+    #[test]
+    fn test_high_tag_non_minimal() {
+        let bytes: Vec<u8> = vec![0x9F, 0x80, 0x81, 0x00];
+        assert_eq!(parse_type_header(&bytes[..]),
+                   IResult::Done(&[][..], (TagClass::Context, TagStructure::Primitive, 128)));
+        assert_eq!(parse_type_header_strict(&bytes[..]),
+                   IResult::Error(ErrorKind::Custom(Error::NonMinimalEncoding { offset: 1 })));
+
+        // Numbers below 31 have to use the short form.
+        let bytes: Vec<u8> = vec![0x1F, 0x05];
+        assert_eq!(parse_type_header(&bytes[..]),
+                   IResult::Done(&[][..], (TagClass::Universal, TagStructure::Primitive, 5)));
+        assert_eq!(parse_type_header_strict(&bytes[..]),
+                   IResult::Error(ErrorKind::Custom(Error::NonMinimalEncoding { offset: 1 })));
+    }
+
+    #[test]
+    fn test_high_tag_overflow() {
+        let bytes: Vec<u8> = vec![0x1F, 0x82, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x00];
+        assert_eq!(parse_type_header(&bytes[..]), IResult::Error(ErrorKind::Custom(Error::TagOverflow { offset: 10 })));
+    }
+
+    #[test]
+    fn test_high_tag_incomplete() {
+        let bytes: Vec<u8> = vec![0x1F, 0x81];
+        assert_eq!(parse_type_header(&bytes[..]), IResult::Incomplete(Needed::Size(3)));
+    }
+
+    #[test]
+    fn test_high_tag_parse() {
+        let bytes: Vec<u8> = vec![0x5F, 0x81, 0x00, 0x01, 0xFF];
+        let result_tag = StructureTag {
+            class: TagClass::Application,
+            id: 128u64,
+            payload: PL::P(vec![0xFF]),
+        };
+
+        assert_eq!(parse_tag(&bytes[..]), IResult::Done(&[][..], result_tag));
+    }
+
+    #[test]
+    fn test_indefinite_length() {
+        let bytes: Vec<u8> = vec![0x30, 0x80, 0x02, 0x01, 0x05, 0x04, 0x02, 0x68, 0x69, 0x00, 0x00, 0xFF];
+        let result_tag = StructureTag {
+            class: TagClass::Universal,
+            id: 16u64,
+            payload: PL::C(vec![
+                StructureTag {
+                    class: TagClass::Universal,
+                    id: 2u64,
+                    payload: PL::P(vec![0x05]),
+                },
+                StructureTag {
+                    class: TagClass::Universal,
+                    id: 4u64,
+                    payload: PL::P(vec![0x68, 0x69]),
+                },
+            ]),
+        };
+
+        assert_eq!(parse_tag(&bytes[..]), IResult::Done(&[0xFF][..], result_tag));
+    }
+
+    #[test]
+    fn test_parse_ref() {
+        let bytes: Vec<u8> = vec![0x30, 0x80, 0x02, 0x01, 0x05, 0x04, 0x02, 0x68, 0x69, 0x00, 0x00, 0xFF];
+        let (rest, tag) = match parse_tag_ref(&bytes[..]) {
+            IResult::Done(rest, tag) => (rest, tag),
+            r => panic!("unexpected result {:?}", r),
+        };
+        assert_eq!(rest, &[0xFF][..]);
+
+        // The content is borrowed from the input, not copied.
+        match tag.payload {
+            PLRef::C(ref tags) => match tags[1].payload {
+                PLRef::P(v) => assert_eq!(v.as_ptr(), bytes[7..].as_ptr()),
+                PLRef::C(_) => panic!("expected a primitive tag"),
+            },
+            PLRef::P(_) => panic!("expected a constructed tag"),
+        }
+
+        assert_eq!(IResult::Done(rest, tag.to_owned()), parse_tag(&bytes[..]));
+        assert_eq!(parse_tag_ref(&[0x30, 0x03, 0x02, 0x01][..]), IResult::Incomplete(Needed::Size(5)));
+    }
+
+    #[test]
+    fn test_nested_indefinite_length() {
+        // An indefinite SEQUENCE inside a definite one, containing an empty indefinite SET.
+        let bytes: Vec<u8> = vec![0x30, 0x08, 0x30, 0x80, 0x31, 0x80, 0x00, 0x00, 0x00, 0x00];
+        let result_tag = StructureTag {
+            class: TagClass::Universal,
+            id: 16u64,
+            payload: PL::C(vec![StructureTag {
+                class: TagClass::Universal,
+                id: 16u64,
+                payload: PL::C(vec![StructureTag {
+                    class: TagClass::Universal,
+                    id: 17u64,
+                    payload: PL::C(vec![]),
+                }]),
+            }]),
+        };
+
+        assert_eq!(parse_tag(&bytes[..]), IResult::Done(&[][..], result_tag));
+    }
+
+    #[test]
+    fn test_indefinite_length_errors() {
+        let primitive: Vec<u8> = vec![0x04, 0x80, 0x68, 0x69, 0x00, 0x00];
+        assert_eq!(parse_tag(&primitive[..]), IResult::Error(ErrorKind::Custom(Error::InvalidLength { offset: 1 })));
+
+        let bad_eoc: Vec<u8> = vec![0x30, 0x80, 0x00, 0x01, 0x00];
+        assert_eq!(parse_tag(&bad_eoc[..]), IResult::Error(ErrorKind::Custom(Error::InvalidEndOfContents { offset: 2 })));
+
+        let unterminated: Vec<u8> = vec![0x30, 0x80, 0x02, 0x01, 0x05, 0x00];
+        assert!(parse_tag(&unterminated[..]).is_incomplete());
+    }
+
+    #[test]
+    fn test_length_forms() {
+        assert_eq!(parse_length(&[0x05]), IResult::Done(&[][..], Length::Definite(5)));
+        assert_eq!(parse_length(&[0x81, 0x80]), IResult::Done(&[][..], Length::Definite(0x80)));
+        assert_eq!(parse_length(&[0x83, 0x01, 0x00, 0x00]), IResult::Done(&[][..], Length::Definite(0x10000)));
+        assert_eq!(parse_length(&[0x84, 0x00, 0x00, 0x00, 0x05]), IResult::Done(&[][..], Length::Definite(5)));
+        assert_eq!(parse_length(&[0x85, 0x01, 0x02, 0x03, 0x04, 0x05]),
+                   IResult::Done(&[][..], Length::Definite(0x0102030405)));
+        assert_eq!(parse_length(&[0x87, 0, 0, 0, 0, 0, 0, 0x07]), IResult::Done(&[][..], Length::Definite(7)));
+        assert_eq!(parse_length(&[0x88, 0, 0, 0, 0, 0, 0, 0, 0x08]), IResult::Done(&[][..], Length::Definite(8)));
+        assert_eq!(parse_length(&[0x80]), IResult::Done(&[][..], Length::Indefinite));
+    }
+
+    #[test]
+    fn test_length_errors() {
+        assert_eq!(parse_length(&[0x89, 0, 0, 0, 0, 0, 0, 0, 0, 0x09]),
+                   IResult::Error(ErrorKind::Custom(Error::InvalidLength { offset: 0 })));
+        assert_eq!(parse_length(&[0xFF, 0, 0, 0, 0, 0, 0, 0, 0, 0x09]),
+                   IResult::Error(ErrorKind::Custom(Error::InvalidLength { offset: 0 })));
+        assert!(parse_length(&[0x82, 0x01]).is_incomplete());
+    }
+
+    #[test]
+    fn test_error_offsets() {
+        // The inner INTEGER claims three octets but its SEQUENCE only holds two more.
+        let truncated: Vec<u8> = vec![0x30, 0x05, 0x04, 0x00, 0x02, 0x03, 0x01, 0x02];
+        assert_eq!(parse_tag(&truncated[..]), IResult::Error(ErrorKind::Custom(Error::Truncated { offset: 4 })));
+
+        // Reserved length octet two levels deep.
+        let nested: Vec<u8> = vec![0x30, 0x06, 0x30, 0x04, 0x04, 0xFF, 0x00, 0x00];
+        assert_eq!(parse_tag(&nested[..]), IResult::Error(ErrorKind::Custom(Error::InvalidLength { offset: 5 })));
+    }
+
+    #[test]
+    fn test_consumer_error() {
+        let mut parser = Parser::new();
+        let bytes: Vec<u8> = vec![0x04, 0x80, 0x00, 0x00];
+
+        match *parser.handle(Input::Element(&bytes[..])) {
+            ConsumerState::Error(ref e) => assert_eq!(e.offset(), Some(1)),
+            ref s => panic!("unexpected state {:?}", s),
+        }
+    }
+
+    #[test]
+    fn test_config_limits() {
+        // Three nested SEQUENCEs around an INTEGER.
+        let bytes: Vec<u8> = vec![0x30, 0x07, 0x30, 0x05, 0x30, 0x03, 0x02, 0x01, 0x05];
+        let config = |f: &dyn Fn(&mut ParserConfig)| {
+            let mut config = ParserConfig::default();
+            f(&mut config);
+            config
+        };
+        let err = |e| IResult::Error(ErrorKind::Custom(e));
+
+        assert!(parse_tag_with_config(&bytes[..], &config(&|c| c.max_depth = 3)).is_done());
+        assert_eq!(parse_tag_with_config(&bytes[..], &config(&|c| c.max_depth = 2)),
+                   err(Error::DepthExceeded { offset: 4 }));
+        assert_eq!(parse_tag_with_config(&bytes[..], &config(&|c| c.max_tags = 3)),
+                   err(Error::TooManyTags { offset: 6 }));
+        assert_eq!(parse_tag_with_config(&bytes[..], &config(&|c| c.max_element_size = 4)),
+                   err(Error::ElementTooLarge { offset: 0 }));
+        let size = ::std::mem::size_of::<StructureTag>();
+        assert!(parse_tag_with_config(&bytes[..], &config(&|c| c.max_allocation = 4 * size + 1)).is_done());
+        assert_eq!(parse_tag_with_config(&bytes[..], &config(&|c| c.max_allocation = 4 * size)),
+                   err(Error::AllocationExceeded { offset: 6 }));
+        assert_eq!(parse_tag_ref_with_config(&bytes[..], &config(&|c| c.max_depth = 2)),
+                   IResult::Error(ErrorKind::Custom(Error::DepthExceeded { offset: 4 })));
+        assert!(parse_tag_ref_with_config(&bytes[..], &config(&|c| c.max_allocation = 4 * size)).is_done());
+
+        // Indefinite lengths are checked once the end of the content is found.
+        let bytes: Vec<u8> = vec![0x30, 0x80, 0x04, 0x03, 0x61, 0x62, 0x63, 0x00, 0x00];
+        assert_eq!(parse_tag_with_config(&bytes[..], &config(&|c| c.max_element_size = 4)),
+                   err(Error::ElementTooLarge { offset: 0 }));
+        assert!(parse_tag_with_config(&bytes[..], &config(&|c| c.max_element_size = 5)).is_done());
+    }
+
+    /// `depth` SEQUENCEs nested inside each other around a NULL, with definite lengths.
+    fn deep_sequence(depth: usize) -> Vec<u8> {
+        let mut headers = Vec::with_capacity(depth);
+        let mut len = 2;
+        for _ in 0..depth {
+            let mut header = vec![0x30];
+            ::write::write_length(&mut header, len).unwrap();
+            len += header.len();
+            headers.push(header);
+        }
+
+        let mut bytes: Vec<u8> = headers.into_iter().rev().flatten().collect();
+        bytes.extend(&[0x05, 0x00]);
+        bytes
+    }
+
+    #[test]
+    fn test_deep_nesting() {
+        let depth = 100_000;
+        let definite = deep_sequence(depth);
+        let mut indefinite: Vec<u8> = [0x30, 0x80].iter().cycle().take(2 * depth).cloned().collect();
+        indefinite.extend(&[0x05, 0x00]);
+        indefinite.extend(vec![0x00; 2 * depth]);
+
+        let config = ParserConfig::unlimited();
+        for bytes in [&definite, &indefinite].iter() {
+            let (rest, tag) = parse_tag_with_config(&bytes[..], &config).unwrap();
+            assert!(rest.is_empty());
+
+            assert_eq!(encode_to_vec(&tag, Rules::Ber), definite);
+            assert_eq!(encode_to_vec(&tag, Rules::Der), definite);
+            assert_eq!(encode_to_vec(&tag, Rules::Cer), indefinite);
+
+            let mut writer = IndefiniteWriter::new(Vec::new());
+            writer.write_tag(tag).unwrap();
+            assert_eq!(writer.into_inner().unwrap(), indefinite);
+        }
+
+        // A string made up of nested segments.
+        let mut segmented: Vec<u8> = [0x24, 0x80].iter().cycle().take(2 * depth).cloned().collect();
+        segmented.extend(&[0x04, 0x01, 0x61]);
+        segmented.extend(vec![0x00; 2 * depth]);
+        let tag = parse_tag_with_config(&segmented[..], &config).unwrap().1;
+        assert_eq!(encode_to_vec(&tag, Rules::Der), vec![0x04, 0x01, 0x61]);
+        assert_eq!(OctetString::from_structure(tag).map(|s| s.inner), Ok(vec![0x61]));
+
+        // The default configuration stops at 128 levels.
+        match parse_tag(&indefinite[..]) {
+            IResult::Error(ErrorKind::Custom(e)) => assert_eq!(e, Error::DepthExceeded { offset: 2 * 128 }),
+            _ => panic!("expected the depth limit to be exceeded"),
+        }
+    }
+
+    #[test]
+    fn test_drop_deep_nesting() {
+        let bytes = deep_sequence(100_000);
+        let tag = parse_tag_with_config(&bytes[..], &ParserConfig::unlimited()).unwrap().1;
+        thread::Builder::new().stack_size(64 * 1024).spawn(move || drop(tag)).unwrap().join().unwrap();
+    }
+
+    #[test]
+    fn test_consumer_limits() {
+        // A length of 2^60 is rejected instead of waited for.
+        let mut parser = Parser::new();
+        let bytes: Vec<u8> = vec![0x04, 0x88, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
+        match *parser.handle(Input::Element(&bytes[..])) {
+            ConsumerState::Error(ref e) => assert_eq!(*e, Error::ElementTooLarge { offset: 0 }),
+            ref s => panic!("unexpected state {:?}", s),
+        }
+
+        let mut parser = Parser::with_config(ParserConfig::unlimited());
+        match *parser.handle(Input::Element(&bytes[..])) {
+            ConsumerState::Continue(Move::Await(Needed::Size(n))) => assert_eq!(n, 10 + (1 << 60)),
+            ref s => panic!("unexpected state {:?}", s),
+        }
+    }
+
+    #[test]
+    fn test_parse_der() {
+        let der: Vec<u8> = vec![0x30, 0x81, 0x80, 0x04, 0x7E];
+        let mut bytes = der.clone();
+        bytes.extend(vec![0x61; 0x7E]);
+        assert!(parse_tag_der(&bytes[..]).is_done());
+        // A SET sorted by tag, and the same members sorted by encoding as a SET OF of a CHOICE.
+        assert!(parse_tag_der(&[0x31, 0x05, 0xA1, 0x00, 0x82, 0x01, 0x00][..]).is_done());
+        assert!(parse_tag_der(&[0x31, 0x05, 0x82, 0x01, 0x00, 0xA1, 0x00][..]).is_done());
+
+        let cases: Vec<(Vec<u8>, Error)> = vec![
+            // Long form for a length below 128
+            (vec![0x04, 0x81, 0x01, 0x61], Error::NonMinimalEncoding { offset: 1 }),
+            // Leading zero length octet
+            (vec![0x30, 0x05, 0x04, 0x82, 0x00, 0x01, 0x61], Error::NonMinimalEncoding { offset: 4 }),
+            // Indefinite length
+            (vec![0x30, 0x80, 0x00, 0x00], Error::NonCanonical { offset: 1 }),
+            // Tag number 5 in the extended form
+            (vec![0x9F, 0x05, 0x00], Error::NonMinimalEncoding { offset: 1 }),
+            // Boolean TRUE as 0x01
+            (vec![0x30, 0x03, 0x01, 0x01, 0x01], Error::NonCanonical { offset: 4 }),
+            // Integer 5 with a leading zero octet
+            (vec![0x30, 0x04, 0x02, 0x02, 0x00, 0x05], Error::NonMinimalEncoding { offset: 4 }),
+            // Unsorted SET OF
+            (vec![0x31, 0x06, 0x04, 0x01, 0x62, 0x04, 0x01, 0x61], Error::NonCanonical { offset: 5 }),
+            // SET sorted neither by tag nor by encoding
+            (vec![0x31, 0x05, 0xA2, 0x00, 0x81, 0x01, 0x00], Error::NonCanonical { offset: 4 }),
+            // Integer without content octets
+            (vec![0x02, 0x00], Error::InvalidContent { offset: 2 }),
+            // Enumerated without content octets
+            (vec![0x0A, 0x00], Error::InvalidContent { offset: 2 }),
+            // NULL with content
+            (vec![0x05, 0x01, 0x00], Error::InvalidContent { offset: 2 }),
+        ];
+        for (bytes, err) in cases {
+            assert_eq!(parse_tag_der(&bytes[..]), IResult::Error(ErrorKind::Custom(err)));
+            assert!(parse_tag(&bytes[..]).is_done());
+        }
+    }
+
+    #[test]
+    fn test_parse_cer() {
+        let mut long_primitive: Vec<u8> = vec![0x04, 0x82, 0x03, 0xE9];
+        long_primitive.extend(vec![0x61; 1001]);
+
+        let mut short_segment: Vec<u8> = vec![0x24, 0x80, 0x04, 0x82, 0x01, 0xF4];
+        short_segment.extend(vec![0x61; 500]);
+        short_segment.extend(vec![0x04, 0x82, 0x01, 0xF4]);
+        short_segment.extend(vec![0x61; 500]);
+        short_segment.extend(vec![0x00, 0x00]);
+
+        let cases: Vec<(Vec<u8>, Error)> = vec![
+            // Definite length on a constructed tag
+            (vec![0x30, 0x03, 0x02, 0x01, 0x01], Error::NonCanonical { offset: 1 }),
+            // Segmented string that would fit into one primitive
+            (vec![0x24, 0x80, 0x04, 0x01, 0x61, 0x00, 0x00], Error::NonCanonical { offset: 0 }),
+            // Primitive string over 1000 octets
+            (long_primitive, Error::NonCanonical { offset: 0 }),
+            // First segment shorter than 1000 octets
+            (short_segment, Error::NonCanonical { offset: 2 }),
+            // Boolean TRUE as 0x01
+            (vec![0x30, 0x80, 0x01, 0x01, 0x01, 0x00, 0x00], Error::NonCanonical { offset: 4 }),
+            // Unsorted SET OF
+            (vec![0x31, 0x80, 0x04, 0x01, 0x62, 0x04, 0x01, 0x61, 0x00, 0x00], Error::NonCanonical { offset: 5 }),
+        ];
+        for (bytes, err) in cases {
+            assert_eq!(parse_tag_cer(&bytes[..]), IResult::Error(ErrorKind::Custom(err)));
+            assert!(parse_tag(&bytes[..]).is_done());
+        }
+    }
+
+    #[test]
+    fn test_validate_der() {
+        let der: Vec<u8> = vec![0x31, 0x06, 0x01, 0x01, 0xFF, 0x02, 0x01, 0x05];
+        let tag = parse_tag(&der[..]).unwrap().1;
+        assert_eq!(validate_der(&tag), Ok(()));
+
+        let cases: Vec<(Vec<u8>, Error)> = vec![
+            (vec![0x30, 0x03, 0x01, 0x01, 0x01], Error::NonCanonical { offset: 4 }),
+            (vec![0x31, 0x06, 0x02, 0x01, 0x05, 0x01, 0x01, 0xFF], Error::NonCanonical { offset: 5 }),
+            (vec![0x30, 0x04, 0x02, 0x02, 0x00, 0x05], Error::NonMinimalEncoding { offset: 4 }),
+            (vec![0x30, 0x05, 0x24, 0x03, 0x04, 0x01, 0x61], Error::NonCanonical { offset: 2 }),
+            (vec![0x30, 0x05, 0x03, 0x03, 0x04, 0xAA, 0xB1], Error::NonCanonical { offset: 6 }),
+            (b"\x30\x0D\x17\x0B0109090146Z".to_vec(), Error::NonCanonical { offset: 14 }),
+            (vec![0x30, 0x05, 0x09, 0x03, 0x80, 0x00, 0x02], Error::NonCanonical { offset: 5 }),
+            (vec![0x30, 0x04, 0x09, 0x02, 0x80, 0x00], Error::InvalidContent { offset: 6 }),
+        ];
+        for (bytes, err) in cases {
+            let tag = parse_tag(&bytes[..]).unwrap().1;
+            assert_eq!(validate_der(&tag), Err(err));
+        }
+    }
 
     #[test]
     fn test_primitive() {
@@ -0,0 +1,225 @@
+//! Lazy traversal of encoded tags.
+//!
+//! `read_tag` only decodes the identifier and length octets of a tag and finds where its content
+//! ends. The children of a constructed tag are read one at a time as its `children` are iterated,
+//! so anything not looked at is skipped without being decoded.
+
+use common::{TagClass, TagStructure, Length};
+use error::Error;
+use structure::StructureTagRef;
+use parse::{parse_type_header, parse_length, parse_tag_ref, offset_by, fail};
+
+use nom::{IResult, Needed};
+
+/// A tag whose content has not been decoded.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct RawTag<'a> {
+    pub class: TagClass,
+    pub structure: TagStructure,
+    pub id: u64,
+    /// The content octets, without the end-of-contents octets of the indefinite length form.
+    pub content: &'a [u8],
+    /// The whole encoding of the tag, identifier and length octets included.
+    pub raw: &'a [u8],
+}
+
+impl<'a> RawTag<'a> {
+    /// Iterate over the children of a constructed tag.
+    pub fn children(&self) -> Result<Children<'a>, Error> {
+        match self.structure {
+            TagStructure::Constructed => Ok(Children { content: self.content, rest: self.content }),
+            TagStructure::Primitive => Err(Error::UnexpectedTag {
+                expected: (self.class, TagStructure::Constructed, self.id),
+                found: (self.class, TagStructure::Primitive, self.id),
+            }),
+        }
+    }
+
+    /// Decode the whole tag. Error offsets are counted from the start of `raw`.
+    pub fn parse(&self) -> Result<StructureTagRef<'a>, Error> {
+        match parse_tag_ref(self.raw) {
+            IResult::Done(_, tag) => Ok(tag),
+            IResult::Error(e) => Err(e.into()),
+            // `raw` was already found to hold a complete tag.
+            IResult::Incomplete(_) => Err(Error::Truncated { offset: 0 }),
+        }
+    }
+}
+
+/// Iterator over the children of a constructed `RawTag`.
+///
+/// Error offsets are counted from the start of the parent's content. Iteration stops after the
+/// first error.
+#[derive(Clone, Debug)]
+pub struct Children<'a> {
+    content: &'a [u8],
+    rest: &'a [u8],
+}
+
+impl<'a> Iterator for Children<'a> {
+    type Item = Result<RawTag<'a>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.rest.is_empty() {
+            return None;
+        }
+
+        let pos = self.content.len() - self.rest.len();
+        let res = match read_tag(self.rest) {
+            IResult::Done(rest, tag) => {
+                self.rest = rest;
+                return Some(Ok(tag));
+            },
+            IResult::Error(e) => Error::from(e).shift(pos),
+            // The content is complete, so a child running past its end is malformed.
+            IResult::Incomplete(_) => Error::Truncated { offset: pos },
+        };
+
+        self.rest = &[];
+        Some(Err(res))
+    }
+}
+
+/// Read the identifier and length octets of a tag and find the end of its content.
+///
+/// Nothing of the content is decoded, except for the headers of nested tags of the indefinite
+/// length form, which have to be read to find the end-of-contents octets.
+pub fn read_tag(i: &[u8]) -> IResult<&[u8], RawTag<'_>, Error> {
+    let (j, (class, structure, id)) = try_parse!(i, parse_type_header);
+    let hdr_len = i.len() - j.len();
+    let (j, len) = try_parse!(j, apply!(offset_by, hdr_len, parse_length));
+    let hdr_len = i.len() - j.len();
+
+    let (content_len, total_len) = match (structure, len) {
+        (_, Length::Definite(len)) => {
+            let len = len as usize;
+            if j.len() < len {
+                return IResult::Incomplete(Needed::Size(hdr_len.saturating_add(len)));
+            }
+            (len, hdr_len + len)
+        },
+        (TagStructure::Primitive, Length::Indefinite) => {
+            return fail(Error::InvalidLength { offset: hdr_len - 1 });
+        },
+        (TagStructure::Constructed, Length::Indefinite) => {
+            let (_, len) = try_parse!(j, apply!(offset_by, hdr_len, indefinite_len));
+            (len, hdr_len + len + 2)
+        },
+    };
+
+    IResult::Done(&i[total_len..], RawTag {
+        class,
+        structure,
+        id,
+        content: &j[..content_len],
+        raw: &i[..total_len],
+    })
+}
+
+/// Find the length of indefinite length content, up to but excluding its end-of-contents octets.
+pub(crate) fn indefinite_len(i: &[u8]) -> IResult<&[u8], usize, Error> {
+    // Number of indefinite length tags whose end-of-contents octets are still to come.
+    let mut open = 1;
+    let mut j = i;
+
+    loop {
+        let pos = i.len() - j.len();
+
+        if j.first() == Some(&0) {
+            match j.get(1) {
+                Some(&0) => {
+                    open -= 1;
+                    j = &j[2..];
+                    if open == 0 {
+                        return IResult::Done(j, pos);
+                    }
+                    continue;
+                },
+                Some(_) => return fail(Error::InvalidEndOfContents { offset: pos }),
+                None => return IResult::Incomplete(Needed::Size(pos + 2)),
+            }
+        }
+
+        let (k, (_, structure, _)) = try_parse!(j, apply!(offset_by, pos, parse_type_header));
+        let hdr_len = i.len() - k.len();
+        let (k, len) = try_parse!(k, apply!(offset_by, hdr_len, parse_length));
+        let hdr_len = i.len() - k.len();
+
+        j = match (structure, len) {
+            (_, Length::Definite(len)) => {
+                let len = len as usize;
+                if k.len() < len {
+                    return IResult::Incomplete(Needed::Size(hdr_len.saturating_add(len)));
+                }
+                &k[len..]
+            },
+            (TagStructure::Primitive, Length::Indefinite) => {
+                return fail(Error::InvalidLength { offset: hdr_len - 1 });
+            },
+            (TagStructure::Constructed, Length::Indefinite) => {
+                open += 1;
+                k
+            },
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use structure::PLRef;
+    use nom::ErrorKind;
+
+    #[test]
+    fn message_id() {
+        // An LDAP message with messageID 5, whose protocolOp would not even parse.
+        let bytes = [0x30, 0x08, 0x02, 0x01, 0x05, 0x60, 0x03, 0x02, 0x05, 0x00, 0xFF];
+        let (rest, msg) = read_tag(&bytes[..]).unwrap();
+        assert_eq!(rest, &[0xFF][..]);
+        assert_eq!((msg.class, msg.structure, msg.id), (TagClass::Universal, TagStructure::Constructed, 16));
+        assert_eq!(msg.raw, &bytes[..10]);
+
+        let mut children = msg.children().unwrap();
+        let id = children.next().unwrap().unwrap();
+        assert_eq!((id.id, id.content), (2, &[0x05][..]));
+        assert_eq!(id.parse().map(|t| t.into_payload()), Ok(PLRef::P(&[0x05][..])));
+
+        let op = children.next().unwrap().unwrap();
+        assert_eq!((op.class, op.id, op.raw), (TagClass::Application, 0, &bytes[5..10]));
+        assert_eq!(op.children().unwrap().next(), Some(Err(Error::Truncated { offset: 0 })));
+        assert_eq!(children.next(), None);
+
+        assert!(id.children().is_err());
+    }
+
+    #[test]
+    fn indefinite_length() {
+        // An indefinite SEQUENCE holding an indefinite SET with an INTEGER, and an OCTET STRING.
+        let bytes = [0x30, 0x80, 0x31, 0x80, 0x02, 0x01, 0x05, 0x00, 0x00, 0x04, 0x01, 0x61, 0x00, 0x00];
+        let (rest, seq) = read_tag(&bytes[..]).unwrap();
+        assert!(rest.is_empty());
+        assert_eq!(seq.content, &bytes[2..12]);
+
+        let children: Vec<_> = seq.children().unwrap().map(Result::unwrap).collect();
+        assert_eq!(children.len(), 2);
+        assert_eq!(children[0].content, &bytes[4..7]);
+        assert_eq!(children[0].raw, &bytes[2..9]);
+        assert_eq!(children[1].content, &[0x61][..]);
+        assert_eq!(seq.parse().map(|t| t.to_owned()), Ok(::parse::parse_tag(&bytes[..]).unwrap().1));
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(read_tag(&[0x30, 0x05, 0x02][..]), IResult::Incomplete(Needed::Size(7)));
+        assert_eq!(read_tag(&[0x30, 0x80, 0x30, 0x80, 0x00, 0x00][..]), IResult::Incomplete(Needed::Size(7)));
+        assert_eq!(read_tag(&[0x30, 0x80, 0x04, 0x80][..]),
+                   IResult::Error(ErrorKind::Custom(Error::InvalidLength { offset: 3 })));
+        assert_eq!(read_tag(&[0x30, 0x80, 0x00, 0x01][..]),
+                   IResult::Error(ErrorKind::Custom(Error::InvalidEndOfContents { offset: 2 })));
+        // The largest length there is, which mustn't overflow when the header is added.
+        assert_eq!(read_tag(&[0x04, 0x88, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF][..]),
+                   IResult::Incomplete(Needed::Size(usize::MAX)));
+        assert_eq!(read_tag(&[0x30, 0x80, 0x04, 0x88, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF][..]),
+                   IResult::Incomplete(Needed::Size(usize::MAX)));
+    }
+}
@@ -1,55 +1,117 @@
 use traits::{BERPayload, BERTag};
-use common::{TagStructure, TagClass};
+use common::{TagStructure, Length};
+use error::Error;
 
-use structure::StructureTag;
+use parse::{parse_type_header, parse_length, offset_by, fail};
+use reader::indefinite_len;
+use structures::ImplicitTag;
 
-use parse::{parse_type_header, parse_length};
+use write::write_header;
 
-use write::{write_type, write_length};
+use nom::{IResult, ErrorKind, Needed};
 
-pub struct SpecificTag<T> {
-    class: TagClass,
-    id: u64,
-    structure: TagStructure,
-    inner: T,
-}
+/// A payload put under a tag chosen at runtime, as with IMPLICIT tagging.
+///
+/// This is `structures::ImplicitTag` under the name the typed-tag layer uses: the payload decides
+/// whether the tag is primitive or constructed, and the tag of its own type is replaced by
+/// `class` and `id`.
+pub type SpecificTag<T> = ImplicitTag<T>;
+
+impl<T: BERPayload> BERTag for ImplicitTag<T> {
+    fn encode_into(&self, buf: &mut Vec<u8>) {
+        write_header(buf, self.class, self.inner.structure(), self.id, self.inner.len() as usize);
+        self.inner.encode_into(buf);
+    }
+
+    fn decode(i: &[u8]) -> IResult<&[u8], Self, Error> {
+        let (j, (class, structure, id)) = try_parse!(i, parse_type_header);
+        let type_len = i.len() - j.len();
+        let (j, length) = try_parse!(j, apply!(offset_by, type_len, parse_length));
+        let header = i.len() - j.len();
+
+        let (rest, content) = match (structure, length) {
+            (_, Length::Definite(len)) => {
+                let len = len as usize;
+                if j.len() < len {
+                    return IResult::Incomplete(Needed::Size(header.saturating_add(len)));
+                }
+                (&j[len..], &j[..len])
+            },
+            (TagStructure::Primitive, Length::Indefinite) => {
+                return fail(Error::InvalidLength { offset: header - 1 });
+            },
+            // Only the headers of the content are read to find its end; `T` decodes it.
+            (TagStructure::Constructed, Length::Indefinite) => {
+                let (rest, len) = try_parse!(j, apply!(offset_by, header, indefinite_len));
+                (rest, &j[..len])
+            },
+        };
 
-impl<T: BERPayload> SpecificTag<T> {
-    pub fn wrap(class: TagClass,
-                id: u64,
-                structure: TagStructure,
-                inner: T) -> Self {
-        SpecificTag {
-            class: class,
-            id: id,
-            structure: structure,
-            inner: inner
+        match T::decode(structure, content) {
+            Ok(inner) => IResult::Done(rest, ImplicitTag { class, id, inner }),
+            Err(e) => IResult::Error(ErrorKind::Custom(e.shift(header))),
         }
     }
 }
 
-struct Something {
-    a: u32,
-    b: u32,
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::TagClass;
+    use structures::{FromStructure, Integer, OctetString, Sequence, SequenceOf, Tag};
+    use structure::{StructureTag, PL};
 
-impl Something {
-    fn fill(tag: StructureTag) -> Option<Something> {
-        if let Some(i) = tag
-            .match_class(TagClass::Application)
-            .and_then(|x| x.match_id(42u64))
-        {
-            if let Some(mut j) = i.expect_constructed() {
-                let b = j.pop().unwrap();
-                let a = j.pop().unwrap();
-
-                None
-
-            } else {
-                None
-            }
-        } else {
-            None
-        }
+    use std::default::Default;
+
+    #[test]
+    fn implicit_primitive() {
+        let tag = SpecificTag::new(TagClass::Context, 2, OctetString { inner: vec![0x61], .. Default::default() });
+        let bytes = tag.encode();
+        assert_eq!(bytes, vec![0x82, 0x01, 0x61]);
+
+        let decoded = SpecificTag::<OctetString>::decode(&bytes[..]).unwrap().1;
+        assert_eq!(decoded, tag);
+        assert_eq!(decoded.into_inner().inner, vec![0x61]);
+    }
+
+    #[test]
+    fn implicit_constructed() {
+        let seq = Sequence { inner: vec![Tag::Integer(Integer { inner: 5, .. Default::default() })], .. Default::default() };
+        let tag = SpecificTag::new(TagClass::Application, 1, seq);
+        let bytes = tag.encode();
+        assert_eq!(bytes, vec![0x61, 0x03, 0x02, 0x01, 0x05]);
+
+        let decoded = SpecificTag::<Sequence>::decode(&bytes[..]).unwrap().1;
+        assert_eq!(decoded.class, TagClass::Application);
+        assert_eq!(decoded.encode(), bytes);
+
+        // Indefinite length
+        let indefinite = [0x61, 0x80, 0x02, 0x01, 0x05, 0x00, 0x00];
+        assert_eq!(SpecificTag::<Sequence>::decode(&indefinite[..]).unwrap().1.encode(), bytes);
+
+        let parsed = StructureTag { class: TagClass::Application, id: 1, payload: PL::C(vec![
+            StructureTag { class: TagClass::Universal, id: 2, payload: PL::P(vec![0x05]) },
+        ])};
+        assert_eq!(SpecificTag::<Sequence>::from_structure(parsed).unwrap().encode(), bytes);
+    }
+
+    #[test]
+    fn decode_errors() {
+        assert_eq!(SpecificTag::<OctetString>::decode(&[0x82, 0x02, 0x61][..]), IResult::Incomplete(Needed::Size(4)));
+        assert_eq!(SpecificTag::<OctetString>::decode(&[0x04, 0x88, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF][..]),
+                   IResult::Incomplete(Needed::Size(usize::MAX)));
+        assert_eq!(SpecificTag::<Integer>::decode(&[0x82, 0x00][..]),
+                   IResult::Error(ErrorKind::Custom(Error::InvalidContent { offset: 2 })));
+        assert_eq!(SpecificTag::<Sequence>::decode(&[0xA1, 0x02, 0x02, 0x01][..]),
+                   IResult::Error(ErrorKind::Custom(Error::Truncated { offset: 2 })));
+
+        // Errors in indefinite length content are reported, at offsets into the input.
+        assert_eq!(SpecificTag::<Integer>::decode(&[0x82, 0x80, 0x05, 0x00, 0x00][..]),
+                   IResult::Error(ErrorKind::Custom(Error::InvalidLength { offset: 1 })));
+        assert_eq!(SpecificTag::<Sequence>::decode(&[0xA1, 0x80, 0x02, 0x01, 0x05, 0x00, 0x01][..]),
+                   IResult::Error(ErrorKind::Custom(Error::InvalidEndOfContents { offset: 5 })));
+        assert_eq!(SpecificTag::<SequenceOf<Integer>>::decode(&[0xA1, 0x80, 0x02, 0x00, 0x00, 0x00][..]),
+                   IResult::Error(ErrorKind::Custom(Error::InvalidContent { offset: 2 })));
+        assert_eq!(SpecificTag::<Sequence>::decode(&[0xA1, 0x80, 0x02, 0x01][..]), IResult::Incomplete(Needed::Size(5)));
     }
 }
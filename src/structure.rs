@@ -1,4 +1,9 @@
-use common::TagClass;
+use common::{TagClass, TagStructure};
+use error::Error;
+use write;
+
+use std::mem;
+use std::slice;
 
 #[derive(Clone, PartialEq, Debug, Eq)]
 pub struct StructureTag {
@@ -13,7 +18,36 @@ pub enum PL {
     C(Vec<StructureTag>),
 }
 
+/// Dropping a tag doesn't recurse into its children, which would overflow the stack for deeply
+/// nested input: the descendants are moved onto a heap stack and dropped one by one instead.
+impl Drop for StructureTag {
+    fn drop(&mut self) {
+        let mut stack = match self.payload {
+            PL::C(ref mut children) => mem::take(children),
+            PL::P(_) => return,
+        };
+        while let Some(mut tag) = stack.pop() {
+            if let PL::C(ref mut children) = tag.payload {
+                stack.append(children);
+            }
+        }
+    }
+}
+
 impl StructureTag {
+    /// Whether the tag is primitive or constructed.
+    pub fn structure(&self) -> TagStructure {
+        match self.payload {
+            PL::P(_) => TagStructure::Primitive,
+            PL::C(_) => TagStructure::Constructed,
+        }
+    }
+
+    /// Number of octets the tag takes up when encoded with `write::encode_into`.
+    pub fn encoded_len(&self) -> usize {
+        write::encoded_len(self)
+    }
+
     pub fn match_class(self, class: TagClass) -> Option<Self> {
         if self.class == class { Some(self) }
         else { None }
@@ -24,33 +58,141 @@ impl StructureTag {
         else { None }
     }
 
-    pub fn expect_constructed(self) -> Option<Vec<StructureTag>> {
-        match self.payload {
+    /// Take the payload out of the tag, which can't be moved out of a `StructureTag` as it
+    /// implements `Drop`.
+    pub fn into_payload(mut self) -> PL {
+        mem::replace(&mut self.payload, PL::P(Vec::new()))
+    }
+
+    pub fn expect_constructed(self) -> Result<Vec<StructureTag>, Error> {
+        let (class, id) = (self.class, self.id);
+        match self.into_payload() {
             PL::P(_) => {
-                None
+                Err(Error::UnexpectedTag {
+                    expected: (class, TagStructure::Constructed, id),
+                    found: (class, TagStructure::Primitive, id),
+                })
             },
             PL::C(i) => {
-                Some(i)
+                Ok(i)
             }
         }
     }
 
-    pub fn expect_primitive(self) -> Option<Vec<u8>> {
-        match self.payload {
+    pub fn expect_primitive(self) -> Result<Vec<u8>, Error> {
+        let (class, id) = (self.class, self.id);
+        match self.into_payload() {
             PL::P(i) => {
-                Some(i)
+                Ok(i)
             },
             PL::C(_) => {
-                None
+                Err(Error::UnexpectedTag {
+                    expected: (class, TagStructure::Primitive, id),
+                    found: (class, TagStructure::Constructed, id),
+                })
+            }
+        }
+    }
+}
+
+/// A tag parsed without copying, borrowing its primitive content from the input.
+///
+/// See `parse::parse_tag_ref`.
+#[derive(PartialEq, Debug, Eq)]
+pub struct StructureTagRef<'a> {
+    pub class: TagClass,
+    pub id: u64,
+    pub payload: PLRef<'a>,
+}
+
+#[derive(PartialEq, Debug, Eq)]
+pub enum PLRef<'a> {
+    P(&'a [u8]),
+    C(Vec<StructureTagRef<'a>>),
+}
+
+/// Like for `StructureTag`, dropping a tag moves its descendants onto a heap stack rather than
+/// recursing into them.
+impl<'a> Drop for StructureTagRef<'a> {
+    fn drop(&mut self) {
+        let mut stack = match self.payload {
+            PLRef::C(ref mut children) => mem::take(children),
+            PLRef::P(_) => return,
+        };
+        while let Some(mut tag) = stack.pop() {
+            if let PLRef::C(ref mut children) = tag.payload {
+                stack.append(children);
             }
         }
     }
 }
 
+impl<'a> StructureTagRef<'a> {
+    /// Whether the tag is primitive or constructed.
+    pub fn structure(&self) -> TagStructure {
+        match self.payload {
+            PLRef::P(_) => TagStructure::Primitive,
+            PLRef::C(_) => TagStructure::Constructed,
+        }
+    }
+
+    /// Take the payload out of the tag, which can't be moved out of a `StructureTagRef` as it
+    /// implements `Drop`.
+    pub fn into_payload(mut self) -> PLRef<'a> {
+        mem::replace(&mut self.payload, PLRef::P(&[]))
+    }
+
+    /// Copy the tag into a `StructureTag` that no longer borrows the input.
+    pub fn to_owned(&self) -> StructureTag {
+        // Constructed tags being copied, each with its children left to copy and those copied.
+        let mut stack: Vec<(&StructureTagRef<'a>, slice::Iter<StructureTagRef<'a>>, Vec<StructureTag>)> = Vec::new();
+        let mut next = self;
+
+        loop {
+            let mut done = match next.payload {
+                PLRef::P(v) => StructureTag { class: next.class, id: next.id, payload: PL::P(v.to_vec()) },
+                PLRef::C(ref tags) => {
+                    stack.push((next, tags.iter(), Vec::with_capacity(tags.len())));
+                    match stack.last_mut().and_then(|top| top.1.next()) {
+                        Some(child) => {
+                            next = child;
+                            continue;
+                        },
+                        None => {
+                            let (tag, _, children) = stack.pop().unwrap();
+                            StructureTag { class: tag.class, id: tag.id, payload: PL::C(children) }
+                        },
+                    }
+                },
+            };
+
+            loop {
+                let top = match stack.last_mut() {
+                    Some(top) => top,
+                    None => return done,
+                };
+                top.2.push(done);
+                if let Some(child) = top.1.next() {
+                    next = child;
+                    break;
+                }
+                let (tag, _, children) = stack.pop().unwrap();
+                done = StructureTag { class: tag.class, id: tag.id, payload: PL::C(children) };
+            }
+        }
+    }
+
+    /// Like `to_owned`, but consuming the tag.
+    pub fn into_owned(self) -> StructureTag {
+        self.to_owned()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use common::{TagClass, TagStructure};
+    use error::Error;
 
     #[test]
     fn expect_exact() {
@@ -99,4 +241,60 @@ mod tests {
         assert!(a.is_some());
         assert!(b.is_some());
     }
+
+    #[test]
+    fn expect_wrong_structure() {
+        let tag = StructureTag {
+            class: TagClass::Context,
+            id: 3u64,
+            payload: PL::P(vec![0x01]),
+        };
+
+        assert_eq!(tag.clone().expect_primitive(), Ok(vec![0x01]));
+        assert_eq!(tag.expect_constructed(), Err(Error::UnexpectedTag {
+            expected: (TagClass::Context, TagStructure::Constructed, 3),
+            found: (TagClass::Context, TagStructure::Primitive, 3),
+        }));
+    }
+
+    #[test]
+    fn ref_to_owned() {
+        let content = [0x16, 0x16];
+        let tag = StructureTagRef {
+            class: TagClass::Application,
+            id: 65u64,
+            payload: PLRef::C(vec![
+                StructureTagRef { class: TagClass::Universal, id: 2u64, payload: PLRef::P(&content[..]) },
+            ]),
+        };
+
+        let owned = StructureTag {
+            class: TagClass::Application,
+            id: 65u64,
+            payload: PL::C(vec![
+                StructureTag { class: TagClass::Universal, id: 2u64, payload: PL::P(vec![0x16, 0x16]) },
+            ]),
+        };
+
+        assert_eq!(tag.structure(), TagStructure::Constructed);
+        assert_eq!(tag.to_owned(), owned);
+        assert_eq!(tag.into_owned(), owned);
+    }
+
+    #[test]
+    fn ref_deep_nesting() {
+        let mut tag = StructureTagRef { class: TagClass::Universal, id: 5, payload: PLRef::P(&[]) };
+        for _ in 0..100_000 {
+            tag = StructureTagRef { class: TagClass::Universal, id: 16, payload: PLRef::C(vec![tag]) };
+        }
+
+        let owned = tag.to_owned();
+        let mut out = Vec::new();
+        ::write::encode_into(&mut out, tag.into_owned()).unwrap();
+        let mut copy = Vec::new();
+        ::write::encode_into(&mut copy, owned).unwrap();
+
+        assert_eq!(&out[out.len() - 4..], &[0x30, 0x02, 0x05, 0x00]);
+        assert_eq!(out, copy);
+    }
 }
@@ -0,0 +1,291 @@
+use std::default;
+use universal;
+use structure;
+
+use super::{ASNTag, FromStructure, expect_universal, cer_string, string_segments};
+use common::{TagClass, TagStructure};
+use error::Error;
+
+/// A BIT STRING.
+///
+/// Bits are numbered from zero, starting at the most significant bit of the first octet, which
+/// is also how named bits like those of X.509 KeyUsage are numbered.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BitString {
+    pub id: u64,
+    pub class: TagClass,
+    /// The bits, packed into octets.
+    pub inner: Vec<u8>,
+    /// How many of the low bits of the last octet are padding and not part of the value.
+    pub unused: u8,
+    /// Whether the value belongs to a type with a named bit list. DER and CER drop the trailing
+    /// zero bits of those.
+    pub named: bool,
+}
+
+/// Mask of the bits of the last octet that carry value with `unused` padding bits.
+fn padding_mask(unused: u8) -> u8 {
+    0xFFu8.checked_shl(unused as u32).unwrap_or(0)
+}
+
+impl BitString {
+    /// Create a universal BIT STRING from packed octets and the number of padding bits in the
+    /// last one. The padding bits are cleared.
+    pub fn new(mut inner: Vec<u8>, unused: u8) -> Result<BitString, Error> {
+        if unused > 7 || (inner.is_empty() && unused != 0) {
+            return Err(Error::InvalidContent { offset: 0 });
+        }
+        if let Some(last) = inner.last_mut() {
+            *last &= padding_mask(unused);
+        }
+
+        Ok(BitString { inner, unused, .. Default::default() })
+    }
+
+    /// Create a universal BIT STRING of a type with a named bit list, with the given bits set.
+    pub fn from_named_bits(bits: &[usize]) -> BitString {
+        let mut string = BitString { named: true, .. Default::default() };
+        for &bit in bits {
+            string.set(bit, true);
+        }
+        string
+    }
+
+    /// Number of bits in the string.
+    pub fn len(&self) -> usize {
+        (self.inner.len() * 8).saturating_sub(self.unused as usize)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The value of bit `n`, or `None` if the string is shorter than that.
+    pub fn get(&self, n: usize) -> Option<bool> {
+        if n >= self.len() {
+            return None;
+        }
+        Some(self.inner[n / 8] & (0x80 >> (n % 8)) != 0)
+    }
+
+    /// Set bit `n`, growing the string with zero bits if it is too short.
+    pub fn set(&mut self, n: usize, value: bool) {
+        if n >= self.len() {
+            self.resize(n + 1);
+        }
+        if value {
+            self.inner[n / 8] |= 0x80 >> (n % 8);
+        } else {
+            self.inner[n / 8] &= !(0x80 >> (n % 8));
+        }
+    }
+
+    /// Iterate over all bits, in order.
+    pub fn iter(&self) -> Bits<'_> {
+        Bits { string: self, pos: 0 }
+    }
+
+    /// Drop all zero bits at the end of the string.
+    pub fn trim_trailing_zeros(&mut self) {
+        let len = (0..self.len()).rev()
+            .find(|&n| self.get(n) == Some(true))
+            .map_or(0, |n| n + 1);
+        self.resize(len);
+    }
+
+    fn resize(&mut self, len: usize) {
+        let octets = (len + 7) / 8;
+        self.inner.resize(octets, 0);
+        self.unused = (octets * 8 - len) as u8;
+        if let Some(last) = self.inner.last_mut() {
+            *last &= padding_mask(self.unused);
+        }
+    }
+
+    /// The content octets: the number of padding bits, followed by the bits.
+    fn content(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(self.inner.len() + 1);
+        out.push(self.unused);
+        out.extend_from_slice(&self.inner);
+        if !self.inner.is_empty() {
+            let last = out.len() - 1;
+            out[last] &= padding_mask(self.unused);
+        }
+        out
+    }
+}
+
+/// Iterator over the bits of a `BitString`.
+pub struct Bits<'a> {
+    string: &'a BitString,
+    pos: usize,
+}
+
+impl<'a> Iterator for Bits<'a> {
+    type Item = bool;
+
+    fn next(&mut self) -> Option<bool> {
+        let bit = self.string.get(self.pos)?;
+        self.pos += 1;
+        Some(bit)
+    }
+}
+
+impl ASNTag for BitString {
+    fn into_structure(self) -> structure::StructureTag {
+        structure::StructureTag {
+            id: self.id,
+            class: self.class,
+            payload: structure::PL::P(self.content()),
+        }
+    }
+
+    fn class_and_id(&self) -> (TagClass, u64) {
+        (self.class, self.id)
+    }
+
+    fn structure(&self) -> TagStructure {
+        TagStructure::Primitive
+    }
+
+    fn content_len(&self) -> usize {
+        1 + self.inner.len()
+    }
+
+    fn into_der_structure(mut self) -> structure::StructureTag {
+        if self.named {
+            self.trim_trailing_zeros();
+        }
+        self.into_structure()
+    }
+
+    fn into_cer_structure(mut self) -> structure::StructureTag {
+        if self.named {
+            self.trim_trailing_zeros();
+        }
+        cer_string(self.class, self.id, self.content(), true)
+    }
+}
+
+impl FromStructure for BitString {
+    fn from_structure(tag: structure::StructureTag) -> Result<Self, Error> {
+        expect_universal(&tag, universal::Types::BitString, tag.structure())?;
+        Self::from_implicit_structure(tag)
+    }
+
+    /// Segmented (constructed) strings are joined back into one. Every segment but the last has
+    /// to end on an octet boundary.
+    fn from_implicit_structure(tag: structure::StructureTag) -> Result<Self, Error> {
+        let (id, class) = (tag.id, tag.class);
+        let segments = string_segments(tag, universal::Types::BitString)?;
+
+        let mut inner = Vec::new();
+        let mut unused = 0;
+        let last = segments.len().saturating_sub(1);
+        for (n, segment) in segments.into_iter().enumerate() {
+            let (&pad, bits) = segment.split_first().ok_or(Error::InvalidContent { offset: 0 })?;
+            if pad > 7 || (bits.is_empty() && pad != 0) || (n != last && pad != 0) {
+                return Err(Error::InvalidContent { offset: 0 });
+            }
+            inner.extend_from_slice(bits);
+            unused = pad;
+        }
+
+        // BER allows any value for the padding bits.
+        let string = BitString::new(inner, unused)?;
+        Ok(BitString { id, class, .. string })
+    }
+}
+
+impl default::Default for BitString {
+    fn default() -> Self {
+        BitString {
+            id: universal::Types::BitString as u64,
+            class: TagClass::Universal,
+            inner: Vec::new(),
+            unused: 0,
+            named: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use structure::{StructureTag, PL};
+
+    fn bits_tag(content: Vec<u8>) -> StructureTag {
+        StructureTag { class: TagClass::Universal, id: 3, payload: PL::P(content) }
+    }
+
+    #[test]
+    fn encode_bitstring() {
+        // Padding bits are cleared.
+        let string = BitString::new(vec![0xAA, 0xBF], 4).unwrap();
+        assert_eq!(string.len(), 12);
+        assert_eq!(string.clone().into_structure(), bits_tag(vec![0x04, 0xAA, 0xB0]));
+        assert_eq!(BitString::from_structure(bits_tag(vec![0x04, 0xAA, 0xBF])), Ok(string));
+
+        assert_eq!(BitString::default().into_structure(), bits_tag(vec![0x00]));
+        assert_eq!(BitString::new(vec![], 1), Err(Error::InvalidContent { offset: 0 }));
+        assert_eq!(BitString::new(vec![0x00], 8), Err(Error::InvalidContent { offset: 0 }));
+    }
+
+    #[test]
+    fn bit_access() {
+        let mut string = BitString::new(vec![0xA0], 5).unwrap();
+        assert_eq!(string.iter().collect::<Vec<_>>(), vec![true, false, true]);
+        assert_eq!(string.get(3), None);
+
+        string.set(1, true);
+        string.set(0, false);
+        string.set(9, true);
+        assert_eq!(string.len(), 10);
+        assert_eq!(string.inner, vec![0x60, 0x40]);
+        assert_eq!(string.unused, 6);
+
+        string.set(9, false);
+        string.trim_trailing_zeros();
+        assert_eq!(string.len(), 3);
+        assert_eq!(string.inner, vec![0x60]);
+    }
+
+    #[test]
+    fn named_bits() {
+        // KeyUsage with digitalSignature and keyEncipherment
+        let usage = BitString::from_named_bits(&[0, 2]);
+        assert_eq!(usage.clone().into_der_structure(), bits_tag(vec![0x05, 0xA0]));
+
+        let mut usage = BitString::new(vec![0xA0, 0x00], 0).unwrap();
+        assert_eq!(usage.clone().into_der_structure(), bits_tag(vec![0x00, 0xA0, 0x00]));
+        usage.named = true;
+        assert_eq!(usage.clone().into_der_structure(), bits_tag(vec![0x05, 0xA0]));
+        assert_eq!(usage.into_cer_structure(), bits_tag(vec![0x05, 0xA0]));
+
+        let empty = BitString { named: true, .. BitString::new(vec![0x00], 0).unwrap() };
+        assert_eq!(empty.into_der_structure(), bits_tag(vec![0x00]));
+    }
+
+    #[test]
+    fn decode_segments() {
+        let tag = StructureTag { class: TagClass::Universal, id: 3, payload: PL::C(vec![
+            bits_tag(vec![0x00, 0xAA]),
+            bits_tag(vec![0x04, 0xB0]),
+        ])};
+        assert_eq!(BitString::from_structure(tag).unwrap().into_structure(), bits_tag(vec![0x04, 0xAA, 0xB0]));
+
+        let tag = StructureTag { class: TagClass::Universal, id: 3, payload: PL::C(vec![
+            bits_tag(vec![0x04, 0xA0]),
+            bits_tag(vec![0x00, 0xB0]),
+        ])};
+        assert_eq!(BitString::from_structure(tag), Err(Error::InvalidContent { offset: 0 }));
+
+        let tag = StructureTag { class: TagClass::Universal, id: 3, payload: PL::C(vec![
+            StructureTag { class: TagClass::Universal, id: 4, payload: PL::P(vec![0x00]) },
+        ])};
+        assert!(BitString::from_structure(tag).is_err());
+
+        assert_eq!(BitString::from_structure(bits_tag(vec![])), Err(Error::InvalidContent { offset: 0 }));
+        assert_eq!(BitString::from_structure(bits_tag(vec![0x01])), Err(Error::InvalidContent { offset: 0 }));
+    }
+}
@@ -2,8 +2,9 @@ use std::default;
 use universal;
 use structure;
 
-use super::ASNTag;
-use common::TagClass;
+use super::{ASNTag, FromStructure, expect_universal, expect_structure};
+use common::{TagClass, TagStructure};
+use error::Error;
 
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Boolean {
@@ -20,8 +21,39 @@ impl ASNTag for Boolean {
             payload: structure::PL::P(if self.inner { vec![0xFF] } else { vec![0x00] }),
         }
     }
+
+    fn class_and_id(&self) -> (TagClass, u64) {
+        (self.class, self.id)
+    }
+
+    fn structure(&self) -> TagStructure {
+        TagStructure::Primitive
+    }
+
+    fn content_len(&self) -> usize {
+        1
+    }
 }
 
+impl FromStructure for Boolean {
+    fn from_structure(tag: structure::StructureTag) -> Result<Self, Error> {
+        expect_universal(&tag, universal::Types::Boolean, TagStructure::Primitive)?;
+        Self::from_implicit_structure(tag)
+    }
+
+    fn from_implicit_structure(tag: structure::StructureTag) -> Result<Self, Error> {
+        expect_structure(&tag, TagStructure::Primitive)?;
+        let (id, class) = (tag.id, tag.class);
+        let v = tag.expect_primitive()?;
+
+        if v.len() != 1 {
+            return Err(Error::InvalidContent { offset: 0 });
+        }
+
+        // Any non-zero octet is TRUE in BER.
+        Ok(Boolean { id, class, inner: v[0] != 0 })
+    }
+}
 
 impl default::Default for Boolean {
     fn default() -> Self {
@@ -0,0 +1,138 @@
+use common::{TagClass, TagStructure};
+use error::Error;
+use super::{ASNTag, FromStructure, expect_universal, expect_structure};
+use super::integer::{decode_i64, encode_i64, encoded_i64_len};
+use universal;
+use structure;
+
+use std::default;
+
+/// An ENUMERATED value, as used for LDAP's resultCode or scope.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Enumerated {
+    pub id: u64,
+    pub class: TagClass,
+    pub inner: i64,
+}
+
+/// A Rust enum that maps to the values of an ASN.1 ENUMERATED type.
+pub trait EnumeratedValue: Sized {
+    /// The number this variant is encoded as.
+    fn discriminant(&self) -> i64;
+
+    /// The variant encoded as `value`, if there is one.
+    fn from_discriminant(value: i64) -> Option<Self>;
+}
+
+impl Enumerated {
+    /// Create a universal ENUMERATED from a variant of a mapped Rust enum.
+    pub fn from_value<T: EnumeratedValue>(value: &T) -> Enumerated {
+        Enumerated { inner: value.discriminant(), .. Default::default() }
+    }
+
+    /// Convert into the variant of a mapped Rust enum, failing for numbers it has no variant for.
+    pub fn to_value<T: EnumeratedValue>(&self) -> Result<T, Error> {
+        T::from_discriminant(self.inner).ok_or(Error::UnknownDiscriminant { value: self.inner })
+    }
+}
+
+impl ASNTag for Enumerated {
+    fn into_structure(self) -> structure::StructureTag {
+        // Encoded like an INTEGER.
+        structure::StructureTag {
+            id: self.id,
+            class: self.class,
+            payload: structure::PL::P(encode_i64(self.inner)),
+        }
+    }
+
+    fn class_and_id(&self) -> (TagClass, u64) {
+        (self.class, self.id)
+    }
+
+    fn structure(&self) -> TagStructure {
+        TagStructure::Primitive
+    }
+
+    fn content_len(&self) -> usize {
+        encoded_i64_len(self.inner)
+    }
+}
+
+impl FromStructure for Enumerated {
+    fn from_structure(tag: structure::StructureTag) -> Result<Self, Error> {
+        expect_universal(&tag, universal::Types::Enumerated, TagStructure::Primitive)?;
+        Self::from_implicit_structure(tag)
+    }
+
+    fn from_implicit_structure(tag: structure::StructureTag) -> Result<Self, Error> {
+        expect_structure(&tag, TagStructure::Primitive)?;
+        let (id, class) = (tag.id, tag.class);
+        let inner = decode_i64(&tag.expect_primitive()?)?;
+
+        Ok(Enumerated { id, class, inner })
+    }
+}
+
+impl default::Default for Enumerated {
+    fn default() -> Enumerated {
+        Enumerated {
+            id: universal::Types::Enumerated as u64,
+            class: TagClass::Universal,
+            inner: 0i64,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use structure::{StructureTag, PL};
+
+    #[derive(Debug, PartialEq)]
+    enum Scope {
+        BaseObject,
+        SingleLevel,
+        WholeSubtree,
+    }
+
+    impl EnumeratedValue for Scope {
+        fn discriminant(&self) -> i64 {
+            match *self {
+                Scope::BaseObject => 0,
+                Scope::SingleLevel => 1,
+                Scope::WholeSubtree => 2,
+            }
+        }
+
+        fn from_discriminant(value: i64) -> Option<Scope> {
+            match value {
+                0 => Some(Scope::BaseObject),
+                1 => Some(Scope::SingleLevel),
+                2 => Some(Scope::WholeSubtree),
+                _ => None,
+            }
+        }
+    }
+
+    #[test]
+    fn encode_enumerated() {
+        let tag = Enumerated::from_value(&Scope::WholeSubtree).into_structure();
+        assert_eq!(tag, StructureTag { class: TagClass::Universal, id: 10, payload: PL::P(vec![0x02]) });
+
+        let decoded = Enumerated::from_structure(tag).unwrap();
+        assert_eq!(decoded.to_value::<Scope>(), Ok(Scope::WholeSubtree));
+
+        let tag = StructureTag { class: TagClass::Universal, id: 10, payload: PL::P(vec![0x00, 0x80]) };
+        assert_eq!(Enumerated::from_structure(tag).map(|e| e.inner), Ok(128));
+    }
+
+    #[test]
+    fn unknown_discriminant() {
+        let value = Enumerated { inner: 3, .. Default::default() };
+        assert_eq!(value.to_value::<Scope>(), Err(Error::UnknownDiscriminant { value: 3 }));
+
+        let tag = StructureTag { class: TagClass::Universal, id: 2, payload: PL::P(vec![0x01]) };
+        assert!(Enumerated::from_structure(tag).is_err());
+    }
+}
@@ -1,7 +1,8 @@
 use structure;
 
-use super::{ASNTag, Tag};
-use common::TagClass;
+use super::{ASNTag, FromStructure, Tag, expect_structure};
+use common::{TagClass, TagStructure};
+use error::Error;
 
 // Explitic tags don't implement Default because that just wouldn't make sense.
 #[derive(Clone, Debug, PartialEq)]
@@ -19,4 +20,52 @@ impl ASNTag for ExplicitTag {
             payload: structure::PL::C(vec![self.inner.into_structure()]),
         }
     }
+
+    fn class_and_id(&self) -> (TagClass, u64) {
+        (self.class, self.id)
+    }
+
+    fn structure(&self) -> TagStructure {
+        TagStructure::Constructed
+    }
+
+    fn content_len(&self) -> usize {
+        (*self.inner).encoded_len()
+    }
+
+    fn into_der_structure(self) -> structure::StructureTag {
+        structure::StructureTag {
+            id: self.id,
+            class: self.class,
+            payload: structure::PL::C(vec![self.inner.into_der_structure()]),
+        }
+    }
+
+    fn into_cer_structure(self) -> structure::StructureTag {
+        structure::StructureTag {
+            id: self.id,
+            class: self.class,
+            payload: structure::PL::C(vec![self.inner.into_cer_structure()]),
+        }
+    }
+}
+
+impl FromStructure for ExplicitTag {
+    /// Explicit tags have no universal tag, so this takes any class and id as well.
+    fn from_structure(tag: structure::StructureTag) -> Result<Self, Error> {
+        Self::from_implicit_structure(tag)
+    }
+
+    /// The inner tag is left undecoded.
+    fn from_implicit_structure(tag: structure::StructureTag) -> Result<Self, Error> {
+        expect_structure(&tag, TagStructure::Constructed)?;
+        let (id, class) = (tag.id, tag.class);
+        let mut inner = tag.expect_constructed()?;
+
+        if inner.len() != 1 {
+            return Err(Error::InvalidContent { offset: 0 });
+        }
+
+        Ok(ExplicitTag { id, class, inner: Box::new(Tag::StructureTag(inner.remove(0))) })
+    }
 }
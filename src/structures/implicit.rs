@@ -0,0 +1,116 @@
+use std::default::Default;
+use structure;
+
+use super::{ASNTag, FromStructure};
+use common::TagClass;
+use error::Error;
+
+/// A value encoded under an IMPLICIT tag.
+///
+/// The tag of the inner type is replaced by `class` and `id`, but whether it is primitive or
+/// constructed stays as the inner type has it. Unlike `ExplicitTag`, no extra layer is added.
+///
+/// Around a `traits::BERPayload` it is also a `traits::BERTag`, as `specific::SpecificTag`.
+// Implicit tags don't implement Default for the same reason explicit tags don't.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ImplicitTag<T> {
+    pub id: u64,
+    pub class: TagClass,
+    pub inner: T,
+}
+
+impl<T> ImplicitTag<T> {
+    pub fn new(class: TagClass, id: u64, inner: T) -> ImplicitTag<T> {
+        ImplicitTag { id, class, inner }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+fn retag(tag: structure::StructureTag, class: TagClass, id: u64) -> structure::StructureTag {
+    structure::StructureTag { id, class, payload: tag.into_payload() }
+}
+
+impl<T: ASNTag> ASNTag for ImplicitTag<T> {
+    fn into_structure(self) -> structure::StructureTag {
+        retag(self.inner.into_structure(), self.class, self.id)
+    }
+
+    fn class_and_id(&self) -> (TagClass, u64) {
+        (self.class, self.id)
+    }
+
+    fn into_der_structure(self) -> structure::StructureTag {
+        retag(self.inner.into_der_structure(), self.class, self.id)
+    }
+
+    fn into_cer_structure(self) -> structure::StructureTag {
+        retag(self.inner.into_cer_structure(), self.class, self.id)
+    }
+}
+
+impl<T: ASNTag + FromStructure + Default> FromStructure for ImplicitTag<T> {
+    /// Implicit tags have no universal tag, so this takes any class and id as well.
+    fn from_structure(tag: structure::StructureTag) -> Result<Self, Error> {
+        Self::from_implicit_structure(tag)
+    }
+
+    /// The content is decoded as `T`, which also checks that it has the structure `T` expects.
+    /// The inner value gets the universal class and id of `T` back.
+    fn from_implicit_structure(tag: structure::StructureTag) -> Result<Self, Error> {
+        let (id, class) = (tag.id, tag.class);
+        let own = T::default().into_structure();
+        let inner = T::from_structure(retag(tag, own.class, own.id))?;
+
+        Ok(ImplicitTag { id, class, inner })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use structure::{StructureTag, PL};
+    use structures::{Integer, OctetString, Sequence, Tag};
+    use common::TagStructure;
+
+    #[test]
+    fn implicit_primitive() {
+        let string = OctetString { inner: vec![0x61], .. Default::default() };
+        let tag = ImplicitTag::new(TagClass::Context, 2, string.clone());
+        let structure = StructureTag { class: TagClass::Context, id: 2, payload: PL::P(vec![0x61]) };
+        assert_eq!(tag.clone().into_structure(), structure);
+
+        assert_eq!(ImplicitTag::<OctetString>::from_structure(structure), Ok(tag));
+    }
+
+    #[test]
+    fn implicit_constructed() {
+        let seq = Sequence { inner: vec![Tag::Integer(Integer { inner: 5, .. Default::default() })], .. Default::default() };
+        let tag = ImplicitTag::new(TagClass::Application, 1, seq.clone());
+        let structure = tag.clone().into_structure();
+        assert_eq!((structure.class, structure.id, structure.structure()),
+                   (TagClass::Application, 1, TagStructure::Constructed));
+
+        let decoded = ImplicitTag::<Sequence>::from_structure(structure).unwrap();
+        assert_eq!(decoded.class, TagClass::Application);
+        assert_eq!(decoded.into_inner().into_structure(), seq.into_structure());
+    }
+
+    #[test]
+    fn wrong_structure() {
+        let structure = StructureTag { class: TagClass::Context, id: 2, payload: PL::C(vec![]) };
+        assert_eq!(ImplicitTag::<OctetString>::from_structure(structure.clone()).map(|t| t.inner.inner), Ok(vec![]));
+        assert!(ImplicitTag::<Integer>::from_structure(structure).is_err());
+    }
+
+    #[test]
+    fn in_sequence() {
+        let int = Integer { inner: 5, .. Default::default() };
+        let seq = Sequence { inner: vec![ImplicitTag::new(TagClass::Context, 0, int).into()], .. Default::default() };
+        assert_eq!(seq.into_structure().expect_constructed(), Ok(vec![
+            StructureTag { class: TagClass::Context, id: 0, payload: PL::P(vec![0x05]) },
+        ]));
+    }
+}
@@ -1,11 +1,13 @@
-use common::TagClass;
-use super::ASNTag;
+use common::{TagClass, TagStructure};
+use error::Error;
+use super::{ASNTag, FromStructure, expect_universal, expect_structure};
 use universal;
 use structure;
+use write;
 
 use std::default;
 
-use byteorder::{BigEndian, WriteBytesExt};
+use byteorder::{BigEndian, ByteOrder};
 
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Integer {
@@ -16,20 +18,68 @@ pub struct Integer {
 
 impl ASNTag for Integer {
     fn into_structure(self) -> structure::StructureTag {
-        let mut count = 0u8;
-        let mut rem: i64 = if self.inner >= 0 { self.inner } else { self.inner * -1 };
-        while {count += 1; rem >>= 8; rem > 0 }{}
-
-        let mut out: Vec<u8> = Vec::with_capacity(count as usize);
-
-        out.write_int::<BigEndian>(self.inner, count as usize).unwrap();
-
         structure::StructureTag {
             id: self.id,
             class: self.class,
-            payload: structure::PL::P(out),
+            payload: structure::PL::P(encode_i64(self.inner)),
         }
     }
+
+    fn class_and_id(&self) -> (TagClass, u64) {
+        (self.class, self.id)
+    }
+
+    fn structure(&self) -> TagStructure {
+        TagStructure::Primitive
+    }
+
+    fn content_len(&self) -> usize {
+        encoded_i64_len(self.inner)
+    }
+}
+
+impl FromStructure for Integer {
+    fn from_structure(tag: structure::StructureTag) -> Result<Self, Error> {
+        expect_universal(&tag, universal::Types::Integer, TagStructure::Primitive)?;
+        Self::from_implicit_structure(tag)
+    }
+
+    fn from_implicit_structure(tag: structure::StructureTag) -> Result<Self, Error> {
+        expect_structure(&tag, TagStructure::Primitive)?;
+        let (id, class) = (tag.id, tag.class);
+        let inner = decode_i64(&tag.expect_primitive()?)?;
+
+        Ok(Integer { id, class, inner })
+    }
+}
+
+/// Decode two's complement content octets into an i64.
+pub(crate) fn decode_i64(v: &[u8]) -> Result<i64, Error> {
+    if v.is_empty() {
+        return Err(Error::InvalidContent { offset: 0 });
+    }
+
+    // BER allows redundant leading octets, which may push a value that fits over eight octets.
+    let skip = write::redundant_int_octets(v);
+    let v = &v[skip..];
+    if v.len() > 8 {
+        return Err(Error::Overflow { offset: skip });
+    }
+
+    Ok(BigEndian::read_int(v, v.len()))
+}
+
+/// Encode an i64 as minimal two's complement content octets, dropping the leading octets that only
+/// repeat the sign.
+pub(crate) fn encode_i64(n: i64) -> Vec<u8> {
+    let bytes = n.to_be_bytes();
+    bytes[write::redundant_int_octets(&bytes)..].to_vec()
+}
+
+/// Number of content octets `encode_i64` produces for `n`.
+pub(crate) fn encoded_i64_len(n: i64) -> usize {
+    let bytes = n.to_be_bytes();
+    bytes.len() - write::redundant_int_octets(&bytes)
 }
 
 impl default::Default for Integer {
@@ -41,3 +91,198 @@ impl default::Default for Integer {
         }
     }
 }
+
+/// An INTEGER of arbitrary size, such as an RSA modulus or a certificate serial number.
+///
+/// `inner` holds the value in big-endian two's complement. Redundant leading octets are dropped
+/// when encoding, and an empty `inner` encodes zero.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BigInteger {
+    pub id: u64,
+    pub class: TagClass,
+    pub inner: Vec<u8>,
+}
+
+impl BigInteger {
+    /// Create a universal INTEGER from big-endian two's complement octets.
+    pub fn from_signed_bytes_be(bytes: &[u8]) -> BigInteger {
+        let skip = write::redundant_int_octets(bytes);
+        BigInteger { inner: bytes[skip..].to_vec(), .. Default::default() }
+    }
+
+    /// Create a universal INTEGER from the big-endian octets of a non-negative number.
+    pub fn from_unsigned_bytes_be(bytes: &[u8]) -> BigInteger {
+        let mut inner = Vec::with_capacity(bytes.len() + 1);
+        inner.push(0);
+        inner.extend_from_slice(bytes);
+        BigInteger::from_signed_bytes_be(&inner)
+    }
+
+    pub fn is_negative(&self) -> bool {
+        matches!(self.inner.first(), Some(&b) if b & 0x80 != 0)
+    }
+
+    /// The minimal big-endian two's complement octets of the value.
+    pub fn to_signed_bytes_be(&self) -> Vec<u8> {
+        if self.inner.is_empty() {
+            return vec![0];
+        }
+        self.inner[write::redundant_int_octets(&self.inner)..].to_vec()
+    }
+
+    /// The minimal big-endian octets of the value, or None if it is negative.
+    pub fn to_unsigned_bytes_be(&self) -> Option<Vec<u8>> {
+        if self.is_negative() {
+            return None;
+        }
+        let bytes = self.to_signed_bytes_be();
+        match bytes.iter().position(|&b| b != 0) {
+            Some(n) => Some(bytes[n..].to_vec()),
+            None => Some(vec![0]),
+        }
+    }
+
+    pub fn to_i64(&self) -> Option<i64> {
+        decode_i64(&self.to_signed_bytes_be()).ok()
+    }
+
+    pub fn to_u64(&self) -> Option<u64> {
+        match self.to_unsigned_bytes_be() {
+            Some(ref bytes) if bytes.len() <= 8 => Some(BigEndian::read_uint(bytes, bytes.len())),
+            _ => None,
+        }
+    }
+}
+
+impl From<i64> for BigInteger {
+    fn from(n: i64) -> BigInteger {
+        BigInteger::from_signed_bytes_be(&n.to_be_bytes())
+    }
+}
+
+impl From<u64> for BigInteger {
+    fn from(n: u64) -> BigInteger {
+        BigInteger::from_unsigned_bytes_be(&n.to_be_bytes())
+    }
+}
+
+impl From<Integer> for BigInteger {
+    fn from(i: Integer) -> BigInteger {
+        BigInteger { id: i.id, class: i.class, .. BigInteger::from(i.inner) }
+    }
+}
+
+impl ASNTag for BigInteger {
+    fn into_structure(self) -> structure::StructureTag {
+        structure::StructureTag {
+            id: self.id,
+            class: self.class,
+            payload: structure::PL::P(self.to_signed_bytes_be()),
+        }
+    }
+
+    fn class_and_id(&self) -> (TagClass, u64) {
+        (self.class, self.id)
+    }
+
+    fn structure(&self) -> TagStructure {
+        TagStructure::Primitive
+    }
+
+    fn content_len(&self) -> usize {
+        match self.inner.len() {
+            0 => 1,
+            len => len - write::redundant_int_octets(&self.inner),
+        }
+    }
+}
+
+impl FromStructure for BigInteger {
+    fn from_structure(tag: structure::StructureTag) -> Result<Self, Error> {
+        expect_universal(&tag, universal::Types::Integer, TagStructure::Primitive)?;
+        Self::from_implicit_structure(tag)
+    }
+
+    fn from_implicit_structure(tag: structure::StructureTag) -> Result<Self, Error> {
+        expect_structure(&tag, TagStructure::Primitive)?;
+        let (id, class) = (tag.id, tag.class);
+        let inner = tag.expect_primitive()?;
+
+        if inner.is_empty() {
+            return Err(Error::InvalidContent { offset: 0 });
+        }
+
+        Ok(BigInteger { id, class, inner })
+    }
+}
+
+impl default::Default for BigInteger {
+    fn default() -> BigInteger {
+        BigInteger {
+            id: universal::Types::Integer as u64,
+            class: TagClass::Universal,
+            inner: vec![0],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use structures::{ASNTag, FromStructure};
+
+    use std::default::Default;
+
+    fn encode(n: i64) -> Vec<u8> {
+        Integer { inner: n, .. Default::default() }.into_structure().expect_primitive().unwrap()
+    }
+
+    #[test]
+    fn integer_twos_complement() {
+        assert_eq!(encode(0), vec![0x00]);
+        assert_eq!(encode(127), vec![0x7F]);
+        assert_eq!(encode(128), vec![0x00, 0x80]);
+        assert_eq!(encode(256), vec![0x01, 0x00]);
+        assert_eq!(encode(-1), vec![0xFF]);
+        assert_eq!(encode(-128), vec![0x80]);
+        assert_eq!(encode(-129), vec![0xFF, 0x7F]);
+        assert_eq!(encode(i64::MAX), vec![0x7F, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]);
+        assert_eq!(encode(i64::MIN), vec![0x80, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+
+        for &n in [0, 1, -1, 127, 128, -128, -129, 65535, i64::MAX, i64::MIN].iter() {
+            let int = Integer { inner: n, .. Default::default() };
+            assert_eq!(Integer::from_structure(int.into_structure()), Ok(int));
+        }
+    }
+
+    #[test]
+    fn big_integer() {
+        // A modulus with the high bit set needs a leading zero octet to stay positive.
+        let modulus = BigInteger::from_unsigned_bytes_be(&[0xC5, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08]);
+        let tag = modulus.clone().into_structure();
+        assert_eq!(tag.clone().expect_primitive().unwrap(),
+                   vec![0x00, 0xC5, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08]);
+        assert!(Integer::from_structure(tag.clone()).is_err());
+
+        let decoded = BigInteger::from_structure(tag).unwrap();
+        assert_eq!(decoded, modulus);
+        assert!(!decoded.is_negative());
+        assert_eq!(decoded.to_u64(), None);
+        assert_eq!(decoded.to_unsigned_bytes_be().unwrap(),
+                   vec![0xC5, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08]);
+
+        let counter = BigInteger::from(u64::MAX);
+        assert_eq!(counter.to_signed_bytes_be(), vec![0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]);
+        assert_eq!(counter.to_u64(), Some(u64::MAX));
+        assert_eq!(counter.to_i64(), None);
+
+        let negative = BigInteger::from(-129i64);
+        assert!(negative.is_negative());
+        assert_eq!(negative.to_signed_bytes_be(), vec![0xFF, 0x7F]);
+        assert_eq!(negative.to_i64(), Some(-129));
+        assert_eq!(negative.to_unsigned_bytes_be(), None);
+
+        assert_eq!(BigInteger::from_signed_bytes_be(&[0x00, 0x00, 0x05]).inner, vec![0x05]);
+        assert_eq!(BigInteger::default().to_u64(), Some(0));
+    }
+}
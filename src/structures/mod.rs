@@ -1,4 +1,8 @@
 use structure;
+use common::{TagClass, TagStructure};
+use error::Error;
+use universal;
+use write;
 
 pub mod integer;
 pub mod sequence;
@@ -6,14 +10,29 @@ pub mod octetstring;
 pub mod boolean;
 pub mod null;
 pub mod explicit;
+pub mod implicit;
+pub mod oid;
+pub mod bitstring;
+pub mod enumerated;
+pub mod strings;
+pub mod time;
+pub mod real;
 
 // Reexport everything
-pub use self::integer::Integer;
-pub use self::sequence::{Sequence, SequenceOf, SetOf};
+pub use self::integer::{Integer, BigInteger};
+pub use self::sequence::{Sequence, SequenceOf, SetOf, Set};
 pub use self::octetstring::OctetString;
 pub use self::boolean::Boolean;
 pub use self::null::Null;
 pub use self::explicit::ExplicitTag;
+pub use self::implicit::ImplicitTag;
+pub use self::oid::{ObjectIdentifier, RelativeOid};
+pub use self::bitstring::BitString;
+pub use self::enumerated::{Enumerated, EnumeratedValue};
+pub use self::strings::{Utf8String, NumericString, PrintableString, Ia5String, VisibleString, T61String,
+                        BmpString, UniversalString};
+pub use self::time::{UtcTime, GeneralizedTime};
+pub use self::real::Real;
 
 pub trait ASNTag {
     /// Encode yourself into a generic Tag format.
@@ -23,17 +42,175 @@ pub trait ASNTag {
     /// a more generic tag (with already encoded payload), we don't have to reimplement the
     /// encoding step for class & id every time.
     fn into_structure(self) -> structure::StructureTag;
+
+    /// The class and id of the tag `into_structure` returns.
+    ///
+    /// This, `structure` and `content_len` are provided by converting a copy of the value, which
+    /// the built-in types override to work them out without converting.
+    fn class_and_id(&self) -> (TagClass, u64) where Self: Sized + Clone {
+        let tag = self.clone().into_structure();
+        (tag.class, tag.id)
+    }
+
+    /// Whether the tag `into_structure` returns is primitive or constructed.
+    fn structure(&self) -> TagStructure where Self: Sized + Clone {
+        self.clone().into_structure().structure()
+    }
+
+    /// Number of content octets of the tag `into_structure` returns, in the definite length form.
+    fn content_len(&self) -> usize where Self: Sized + Clone {
+        write::content_len(&self.clone().into_structure())
+    }
+
+    /// Number of octets the tag `into_structure` returns takes up when encoded with
+    /// `write::encode_into`.
+    fn encoded_len(&self) -> usize where Self: Sized + Clone {
+        let len = self.content_len();
+        write::type_len(self.class_and_id().1) + write::length_len(len) + len
+    }
+
+    /// Encode yourself into a generic Tag format in the form DER requires.
+    ///
+    /// `write::encode_der_into` canonicalizes universal tags by itself, but can't tell what an
+    /// implicitly tagged value is. Types whose DER form differs from their BER form, and types
+    /// containing other tags, override this.
+    fn into_der_structure(self) -> structure::StructureTag where Self: Sized {
+        self.into_structure()
+    }
+
+    /// Encode yourself into a generic Tag format in the form CER requires.
+    ///
+    /// Like `into_der_structure`, but for `write::encode_cer`.
+    fn into_cer_structure(self) -> structure::StructureTag where Self: Sized {
+        self.into_structure()
+    }
+}
+
+/// Lets types that contain themselves, like `Tag`, be wrapped.
+impl<T: ASNTag> ASNTag for Box<T> {
+    fn into_structure(self) -> structure::StructureTag {
+        (*self).into_structure()
+    }
+
+    fn into_der_structure(self) -> structure::StructureTag {
+        (*self).into_der_structure()
+    }
+
+    fn into_cer_structure(self) -> structure::StructureTag {
+        (*self).into_cer_structure()
+    }
+}
+
+pub trait FromStructure: Sized {
+    /// Decode yourself from a generic Tag, checking that it carries your universal class and id.
+    fn from_structure(tag: structure::StructureTag) -> Result<Self, Error>;
+
+    /// Decode yourself from a generic Tag of any class and id, as needed for implicitly tagged
+    /// values. The class and id are kept in the decoded value.
+    fn from_implicit_structure(tag: structure::StructureTag) -> Result<Self, Error>;
+}
+
+/// Check that a tag has the given class, structure and id.
+fn expect_tag(tag: &structure::StructureTag, class: TagClass, structure: TagStructure, id: u64) -> Result<(), Error> {
+    let found = (tag.class, tag.structure(), tag.id);
+    if found != (class, structure, id) {
+        return Err(Error::UnexpectedTag { expected: (class, structure, id), found });
+    }
+    Ok(())
+}
+
+/// Check that a tag is the universal tag of a type.
+fn expect_universal(tag: &structure::StructureTag, ty: universal::Types, structure: TagStructure) -> Result<(), Error> {
+    expect_tag(tag, TagClass::Universal, structure, ty as u64)
+}
+
+/// Check that a tag has the given structure, whatever its class and id.
+fn expect_structure(tag: &structure::StructureTag, structure: TagStructure) -> Result<(), Error> {
+    expect_tag(tag, tag.class, structure, tag.id)
+}
+
+/// The CER form of a string: primitive if the content fits into a single segment, a constructed
+/// string of segments otherwise.
+///
+/// `write::encode_cer` splits long universal strings by itself, but can't tell an implicitly
+/// tagged string from any other tag, so the string types do it in `into_cer_structure` already.
+fn cer_string(class: TagClass, id: u64, content: Vec<u8>, bitstring: bool) -> structure::StructureTag {
+    let payload = if content.len() > write::CER_SEGMENT_LEN {
+        structure::PL::C(write::cer_segments(&content, bitstring))
+    } else {
+        structure::PL::P(content)
+    };
+
+    structure::StructureTag { id, class, payload }
+}
+
+/// Collect the content of a string, which BER allows to be split into a constructed list of
+/// segments of the universal type `segment`.
+fn string_content(tag: structure::StructureTag, segment: universal::Types) -> Result<Vec<u8>, Error> {
+    Ok(string_segments(tag, segment)?.concat())
+}
+
+/// Collect the content octets of every segment of a string, in order. A primitive string is a
+/// single segment.
+fn string_segments(tag: structure::StructureTag, segment: universal::Types) -> Result<Vec<Vec<u8>>, Error> {
+    let segment = segment as u64;
+    // Segments left to collect, the next one last.
+    let mut stack = match tag.into_payload() {
+        structure::PL::P(v) => return Ok(vec![v]),
+        structure::PL::C(mut tags) => {
+            tags.reverse();
+            tags
+        },
+    };
+
+    let mut out = Vec::new();
+    while let Some(tag) = stack.pop() {
+        if tag.class != TagClass::Universal || tag.id != segment {
+            return Err(Error::UnexpectedTag {
+                expected: (TagClass::Universal, tag.structure(), segment),
+                found: (tag.class, tag.structure(), tag.id),
+            });
+        }
+        match tag.into_payload() {
+            structure::PL::P(v) => out.push(v),
+            structure::PL::C(inner) => stack.extend(inner.into_iter().rev()),
+        }
+    }
+    Ok(out)
 }
 
 #[derive(Clone, Debug, PartialEq)]
-/// This enum does not cover all ASN.1 Types but only the types needed for LDAPv3.
+/// Any of the ASN.1 types this crate implements, to build up values of mixed types.
+///
+/// Types without a variant of their own, such as CHOICE, or tags from a schema this crate doesn't
+/// know can be held as a `StructureTag`.
 pub enum Tag {
     Integer(integer::Integer),
+    BigInteger(integer::BigInteger),
     Sequence(sequence::Sequence),
+    Set(sequence::Set),
+    SequenceOf(sequence::SequenceOf<Tag>),
+    SetOf(sequence::SetOf<Tag>),
     OctetString(octetstring::OctetString),
     Boolean(boolean::Boolean),
     Null(null::Null),
     ExplicitTag(explicit::ExplicitTag),
+    ImplicitTag(implicit::ImplicitTag<Box<Tag>>),
+    ObjectIdentifier(oid::ObjectIdentifier),
+    RelativeOid(oid::RelativeOid),
+    BitString(bitstring::BitString),
+    Enumerated(enumerated::Enumerated),
+    Utf8String(strings::Utf8String),
+    NumericString(strings::NumericString),
+    PrintableString(strings::PrintableString),
+    Ia5String(strings::Ia5String),
+    VisibleString(strings::VisibleString),
+    T61String(strings::T61String),
+    BmpString(strings::BmpString),
+    UniversalString(strings::UniversalString),
+    UtcTime(time::UtcTime),
+    GeneralizedTime(time::GeneralizedTime),
+    Real(real::Real),
     StructureTag(structure::StructureTag),
 }
 
@@ -41,12 +218,487 @@ impl ASNTag for Tag {
     fn into_structure(self) -> structure::StructureTag {
         match self {
             Tag::Integer(i)      => i.into_structure(),
+            Tag::BigInteger(i)   => i.into_structure(),
             Tag::Sequence(i)     => i.into_structure(),
+            Tag::Set(i)          => i.into_structure(),
+            Tag::SequenceOf(i)   => i.into_structure(),
+            Tag::SetOf(i)        => i.into_structure(),
             Tag::OctetString(i)  => i.into_structure(),
             Tag::Boolean(i)      => i.into_structure(),
             Tag::Null(i)         => i.into_structure(),
             Tag::ExplicitTag(i)  => i.into_structure(),
+            Tag::ImplicitTag(i)  => i.into_structure(),
+            Tag::ObjectIdentifier(i) => i.into_structure(),
+            Tag::RelativeOid(i)  => i.into_structure(),
+            Tag::BitString(i)    => i.into_structure(),
+            Tag::Enumerated(i)   => i.into_structure(),
+            Tag::Utf8String(i) => i.into_structure(),
+            Tag::NumericString(i) => i.into_structure(),
+            Tag::PrintableString(i) => i.into_structure(),
+            Tag::Ia5String(i) => i.into_structure(),
+            Tag::VisibleString(i) => i.into_structure(),
+            Tag::T61String(i) => i.into_structure(),
+            Tag::BmpString(i) => i.into_structure(),
+            Tag::UniversalString(i) => i.into_structure(),
+            Tag::UtcTime(i)      => i.into_structure(),
+            Tag::GeneralizedTime(i) => i.into_structure(),
+            Tag::Real(i)         => i.into_structure(),
+            Tag::StructureTag(s) => s
+        }
+    }
+
+    fn class_and_id(&self) -> (TagClass, u64) {
+        match *self {
+            Tag::Integer(ref i) => i.class_and_id(),
+            Tag::BigInteger(ref i) => i.class_and_id(),
+            Tag::Sequence(ref i) => i.class_and_id(),
+            Tag::Set(ref i) => i.class_and_id(),
+            Tag::SequenceOf(ref i) => i.class_and_id(),
+            Tag::SetOf(ref i) => i.class_and_id(),
+            Tag::OctetString(ref i) => i.class_and_id(),
+            Tag::Boolean(ref i) => i.class_and_id(),
+            Tag::Null(ref i) => i.class_and_id(),
+            Tag::ExplicitTag(ref i) => i.class_and_id(),
+            Tag::ImplicitTag(ref i) => i.class_and_id(),
+            Tag::ObjectIdentifier(ref i) => i.class_and_id(),
+            Tag::RelativeOid(ref i) => i.class_and_id(),
+            Tag::BitString(ref i) => i.class_and_id(),
+            Tag::Enumerated(ref i) => i.class_and_id(),
+            Tag::Utf8String(ref i) => i.class_and_id(),
+            Tag::NumericString(ref i) => i.class_and_id(),
+            Tag::PrintableString(ref i) => i.class_and_id(),
+            Tag::Ia5String(ref i) => i.class_and_id(),
+            Tag::VisibleString(ref i) => i.class_and_id(),
+            Tag::T61String(ref i) => i.class_and_id(),
+            Tag::BmpString(ref i) => i.class_and_id(),
+            Tag::UniversalString(ref i) => i.class_and_id(),
+            Tag::UtcTime(ref i) => i.class_and_id(),
+            Tag::GeneralizedTime(ref i) => i.class_and_id(),
+            Tag::Real(ref i) => i.class_and_id(),
+            Tag::StructureTag(ref s) => (s.class, s.id),
+        }
+    }
+
+    fn structure(&self) -> TagStructure {
+        match *self {
+            Tag::Integer(ref i) => i.structure(),
+            Tag::BigInteger(ref i) => i.structure(),
+            Tag::Sequence(ref i) => i.structure(),
+            Tag::Set(ref i) => i.structure(),
+            Tag::SequenceOf(ref i) => i.structure(),
+            Tag::SetOf(ref i) => i.structure(),
+            Tag::OctetString(ref i) => i.structure(),
+            Tag::Boolean(ref i) => i.structure(),
+            Tag::Null(ref i) => i.structure(),
+            Tag::ExplicitTag(ref i) => i.structure(),
+            Tag::ImplicitTag(ref i) => (*i.inner).structure(),
+            Tag::ObjectIdentifier(ref i) => i.structure(),
+            Tag::RelativeOid(ref i) => i.structure(),
+            Tag::BitString(ref i) => i.structure(),
+            Tag::Enumerated(ref i) => i.structure(),
+            Tag::Utf8String(ref i) => i.structure(),
+            Tag::NumericString(ref i) => i.structure(),
+            Tag::PrintableString(ref i) => i.structure(),
+            Tag::Ia5String(ref i) => i.structure(),
+            Tag::VisibleString(ref i) => i.structure(),
+            Tag::T61String(ref i) => i.structure(),
+            Tag::BmpString(ref i) => i.structure(),
+            Tag::UniversalString(ref i) => i.structure(),
+            Tag::UtcTime(ref i) => i.structure(),
+            Tag::GeneralizedTime(ref i) => i.structure(),
+            Tag::Real(ref i) => i.structure(),
+            Tag::StructureTag(ref s) => s.structure(),
+        }
+    }
+
+    fn content_len(&self) -> usize {
+        match *self {
+            Tag::Integer(ref i) => i.content_len(),
+            Tag::BigInteger(ref i) => i.content_len(),
+            Tag::Sequence(ref i) => i.content_len(),
+            Tag::Set(ref i) => i.content_len(),
+            Tag::SequenceOf(ref i) => i.inner.iter().map(ASNTag::encoded_len).sum(),
+            Tag::SetOf(ref i) => i.inner.iter().map(ASNTag::encoded_len).sum(),
+            Tag::OctetString(ref i) => i.content_len(),
+            Tag::Boolean(ref i) => i.content_len(),
+            Tag::Null(ref i) => i.content_len(),
+            Tag::ExplicitTag(ref i) => i.content_len(),
+            Tag::ImplicitTag(ref i) => (*i.inner).content_len(),
+            Tag::ObjectIdentifier(ref i) => i.content_len(),
+            Tag::RelativeOid(ref i) => i.content_len(),
+            Tag::BitString(ref i) => i.content_len(),
+            Tag::Enumerated(ref i) => i.content_len(),
+            Tag::Utf8String(ref i) => i.content_len(),
+            Tag::NumericString(ref i) => i.content_len(),
+            Tag::PrintableString(ref i) => i.content_len(),
+            Tag::Ia5String(ref i) => i.content_len(),
+            Tag::VisibleString(ref i) => i.content_len(),
+            Tag::T61String(ref i) => i.content_len(),
+            Tag::BmpString(ref i) => i.content_len(),
+            Tag::UniversalString(ref i) => i.content_len(),
+            Tag::UtcTime(ref i) => i.content_len(),
+            Tag::GeneralizedTime(ref i) => i.content_len(),
+            Tag::Real(ref i) => i.content_len(),
+            Tag::StructureTag(ref s) => write::content_len(s),
+        }
+    }
+
+    fn into_der_structure(self) -> structure::StructureTag {
+        match self {
+            Tag::Integer(i)      => i.into_der_structure(),
+            Tag::BigInteger(i)   => i.into_der_structure(),
+            Tag::Sequence(i)     => i.into_der_structure(),
+            Tag::Set(i)          => i.into_der_structure(),
+            Tag::SequenceOf(i)   => i.into_der_structure(),
+            Tag::SetOf(i)        => i.into_der_structure(),
+            Tag::OctetString(i)  => i.into_der_structure(),
+            Tag::Boolean(i)      => i.into_der_structure(),
+            Tag::Null(i)         => i.into_der_structure(),
+            Tag::ExplicitTag(i)  => i.into_der_structure(),
+            Tag::ImplicitTag(i)  => i.into_der_structure(),
+            Tag::ObjectIdentifier(i) => i.into_der_structure(),
+            Tag::RelativeOid(i)  => i.into_der_structure(),
+            Tag::BitString(i)    => i.into_der_structure(),
+            Tag::Enumerated(i)   => i.into_der_structure(),
+            Tag::Utf8String(i) => i.into_der_structure(),
+            Tag::NumericString(i) => i.into_der_structure(),
+            Tag::PrintableString(i) => i.into_der_structure(),
+            Tag::Ia5String(i) => i.into_der_structure(),
+            Tag::VisibleString(i) => i.into_der_structure(),
+            Tag::T61String(i) => i.into_der_structure(),
+            Tag::BmpString(i) => i.into_der_structure(),
+            Tag::UniversalString(i) => i.into_der_structure(),
+            Tag::UtcTime(i)      => i.into_der_structure(),
+            Tag::GeneralizedTime(i) => i.into_der_structure(),
+            Tag::Real(i)         => i.into_der_structure(),
+            Tag::StructureTag(s) => s
+        }
+    }
+
+    fn into_cer_structure(self) -> structure::StructureTag {
+        match self {
+            Tag::Integer(i)      => i.into_cer_structure(),
+            Tag::BigInteger(i)   => i.into_cer_structure(),
+            Tag::Sequence(i)     => i.into_cer_structure(),
+            Tag::Set(i)          => i.into_cer_structure(),
+            Tag::SequenceOf(i)   => i.into_cer_structure(),
+            Tag::SetOf(i)        => i.into_cer_structure(),
+            Tag::OctetString(i)  => i.into_cer_structure(),
+            Tag::Boolean(i)      => i.into_cer_structure(),
+            Tag::Null(i)         => i.into_cer_structure(),
+            Tag::ExplicitTag(i)  => i.into_cer_structure(),
+            Tag::ImplicitTag(i)  => i.into_cer_structure(),
+            Tag::ObjectIdentifier(i) => i.into_cer_structure(),
+            Tag::RelativeOid(i)  => i.into_cer_structure(),
+            Tag::BitString(i)    => i.into_cer_structure(),
+            Tag::Enumerated(i)   => i.into_cer_structure(),
+            Tag::Utf8String(i) => i.into_cer_structure(),
+            Tag::NumericString(i) => i.into_cer_structure(),
+            Tag::PrintableString(i) => i.into_cer_structure(),
+            Tag::Ia5String(i) => i.into_cer_structure(),
+            Tag::VisibleString(i) => i.into_cer_structure(),
+            Tag::T61String(i) => i.into_cer_structure(),
+            Tag::BmpString(i) => i.into_cer_structure(),
+            Tag::UniversalString(i) => i.into_cer_structure(),
+            Tag::UtcTime(i)      => i.into_cer_structure(),
+            Tag::GeneralizedTime(i) => i.into_cer_structure(),
+            Tag::Real(i)         => i.into_cer_structure(),
             Tag::StructureTag(s) => s
         }
     }
 }
+
+/// Members of a SEQUENCE OF or SET OF decoded into `Tag` are left undecoded.
+impl FromStructure for Tag {
+    fn from_structure(tag: structure::StructureTag) -> Result<Self, Error> {
+        Ok(Tag::StructureTag(tag))
+    }
+
+    fn from_implicit_structure(tag: structure::StructureTag) -> Result<Self, Error> {
+        Ok(Tag::StructureTag(tag))
+    }
+}
+
+macro_rules! tag_from {
+    ($($variant:ident($ty:ty)),*) => {
+        $(
+            impl From<$ty> for Tag {
+                fn from(x: $ty) -> Tag {
+                    Tag::$variant(x)
+                }
+            }
+        )*
+    }
+}
+
+tag_from!(
+    Integer(integer::Integer),
+    BigInteger(integer::BigInteger),
+    Sequence(sequence::Sequence),
+    Set(sequence::Set),
+    OctetString(octetstring::OctetString),
+    Boolean(boolean::Boolean),
+    Null(null::Null),
+    ExplicitTag(explicit::ExplicitTag),
+    ObjectIdentifier(oid::ObjectIdentifier),
+    RelativeOid(oid::RelativeOid),
+    BitString(bitstring::BitString),
+    Enumerated(enumerated::Enumerated),
+    Utf8String(strings::Utf8String),
+    NumericString(strings::NumericString),
+    PrintableString(strings::PrintableString),
+    Ia5String(strings::Ia5String),
+    VisibleString(strings::VisibleString),
+    T61String(strings::T61String),
+    BmpString(strings::BmpString),
+    UniversalString(strings::UniversalString),
+    UtcTime(time::UtcTime),
+    GeneralizedTime(time::GeneralizedTime),
+    Real(real::Real),
+    StructureTag(structure::StructureTag)
+);
+
+impl<T: Into<Tag>> From<implicit::ImplicitTag<T>> for Tag {
+    fn from(x: implicit::ImplicitTag<T>) -> Tag {
+        Tag::ImplicitTag(implicit::ImplicitTag { id: x.id, class: x.class, inner: Box::new(x.inner.into()) })
+    }
+}
+
+impl<T: Into<Tag>> From<sequence::SequenceOf<T>> for Tag {
+    fn from(x: sequence::SequenceOf<T>) -> Tag {
+        let inner = x.inner.into_iter().map(Into::into).collect();
+        Tag::SequenceOf(sequence::SequenceOf { id: x.id, class: x.class, inner })
+    }
+}
+
+impl<T: Into<Tag>> From<sequence::SetOf<T>> for Tag {
+    fn from(x: sequence::SetOf<T>) -> Tag {
+        let inner = x.inner.into_iter().map(Into::into).collect();
+        Tag::SetOf(sequence::SetOf { id: x.id, class: x.class, inner })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use structure::{StructureTag, PL};
+    use common::{TagClass, TagStructure};
+    use error::Error;
+
+    use std::default::Default;
+
+    #[test]
+    fn roundtrip_primitives() {
+        let int = Integer { inner: -2, .. Default::default() };
+        assert_eq!(Integer::from_structure(int.into_structure()), Ok(int));
+
+        let boolean = Boolean { inner: true, .. Default::default() };
+        assert_eq!(Boolean::from_structure(boolean.into_structure()), Ok(boolean));
+
+        let null = Null { .. Default::default() };
+        assert_eq!(Null::from_structure(null.clone().into_structure()), Ok(null));
+
+        let string = OctetString { id: 7, class: TagClass::Context, inner: vec![0x61, 0x62] };
+        assert_eq!(OctetString::from_implicit_structure(string.clone().into_structure()), Ok(string));
+    }
+
+    #[test]
+    fn roundtrip_constructed() {
+        let seq = SequenceOf::<Integer> {
+            inner: vec![Integer { inner: 1, .. Default::default() }, Integer { inner: 2, .. Default::default() }],
+            .. Default::default()
+        };
+        let tag = StructureTag { class: TagClass::Universal, id: 16, payload: PL::C(vec![
+            StructureTag { class: TagClass::Universal, id: 2, payload: PL::P(vec![0x01]) },
+            StructureTag { class: TagClass::Universal, id: 2, payload: PL::P(vec![0x02]) },
+        ])};
+        assert_eq!(SequenceOf::<Integer>::from_structure(tag.clone()), Ok(seq));
+
+        let generic = Sequence::from_structure(tag.clone()).unwrap();
+        assert_eq!(generic.into_structure(), tag);
+
+        let set = SetOf::<Boolean> {
+            id: 3,
+            class: TagClass::Application,
+            inner: vec![Boolean { inner: false, .. Default::default() }],
+        };
+        assert_eq!(SetOf::<Boolean>::from_implicit_structure(set.clone().into_structure()), Ok(set));
+
+        let explicit = ExplicitTag {
+            id: 0,
+            class: TagClass::Context,
+            inner: Box::new(Tag::Integer(Integer { inner: 5, .. Default::default() })),
+        };
+        let tag = explicit.into_structure();
+        let decoded = ExplicitTag::from_structure(tag.clone()).unwrap();
+        assert_eq!(decoded.into_structure(), tag);
+    }
+
+    #[test]
+    fn sequence_of_in_tag() {
+        // SEQUENCE OF AttributeValue
+        let values: SequenceOf<OctetString> = vec![
+            OctetString { inner: vec![0x61], .. Default::default() },
+            OctetString { inner: vec![0x62], .. Default::default() },
+        ].into();
+        let tag = Tag::Sequence(Sequence {
+            inner: vec![Tag::OctetString(OctetString { inner: vec![0x63], .. Default::default() }), values.clone().into()],
+            .. Default::default()
+        }).into_structure();
+
+        let mut members = tag.expect_constructed().unwrap();
+        let decoded = SequenceOf::<OctetString>::from_structure(members.pop().unwrap()).unwrap();
+        assert_eq!(decoded, values);
+        assert_eq!(Vec::from(decoded).len(), 2);
+
+        // Nested and untyped
+        let nested: SequenceOf<SetOf<Boolean>> = vec![
+            vec![Boolean { inner: true, .. Default::default() }].into(),
+        ].into();
+        let tag = nested.clone().into_structure();
+        assert_eq!(SequenceOf::<SetOf<Boolean>>::from_structure(tag.clone()), Ok(nested));
+
+        let generic = SequenceOf::<Tag>::from_structure(tag.clone()).unwrap();
+        assert_eq!(Tag::SequenceOf(generic).into_structure(), tag);
+    }
+
+    #[test]
+    fn set_by_tag() {
+        let set = Set {
+            inner: vec![
+                Tag::OctetString(OctetString { id: 2, class: TagClass::Context, inner: vec![0x61] }),
+                Tag::Sequence(Sequence { id: 1, class: TagClass::Context, inner: vec![] }),
+                Tag::Integer(Integer { inner: 5, .. Default::default() }),
+            ],
+            .. Default::default()
+        };
+
+        // By tag, even though the constructed [1] encodes as 0xA1 and the primitive [2] as 0x82.
+        let tag = set.clone().into_der_structure();
+        let ids: Vec<u64> = tag.clone().expect_constructed().unwrap().iter().map(|t| t.id).collect();
+        assert_eq!(ids, vec![2, 1, 2]);
+        assert_eq!(tag.clone().expect_constructed().unwrap()[0].class, TagClass::Universal);
+
+        let mut decoded = Set::from_structure(tag).unwrap();
+        assert_eq!(decoded.get(TagClass::Context, 1).map(Tag::class_and_id), Some((TagClass::Context, 1)));
+        assert_eq!(decoded.decode::<OctetString>(TagClass::Context, 2).unwrap().map(|s| s.inner), Some(vec![0x61]));
+        assert_eq!(decoded.decode::<Integer>(TagClass::Universal, 2).unwrap().map(|i| i.inner), Some(5));
+        assert_eq!(decoded.decode::<Integer>(TagClass::Universal, 2), Ok(None));
+        assert_eq!(decoded.inner.len(), 1);
+    }
+
+    #[test]
+    fn decode_ber_forms() {
+        // Non-minimal integer and a non-0xFF TRUE are fine in BER.
+        let tag = StructureTag { class: TagClass::Universal, id: 2, payload: PL::P(vec![0xFF, 0xFF, 0x80]) };
+        assert_eq!(Integer::from_structure(tag).map(|i| i.inner), Ok(-128));
+
+        let tag = StructureTag { class: TagClass::Universal, id: 1, payload: PL::P(vec![0x01]) };
+        assert_eq!(Boolean::from_structure(tag).map(|b| b.inner), Ok(true));
+
+        let tag = StructureTag { class: TagClass::Universal, id: 4, payload: PL::C(vec![
+            StructureTag { class: TagClass::Universal, id: 4, payload: PL::P(vec![0x61]) },
+            StructureTag { class: TagClass::Universal, id: 4, payload: PL::P(vec![0x62]) },
+        ])};
+        assert_eq!(OctetString::from_structure(tag).map(|s| s.inner), Ok(vec![0x61, 0x62]));
+    }
+
+    #[test]
+    fn decode_errors() {
+        let tag = StructureTag { class: TagClass::Universal, id: 2, payload: PL::P(vec![0x01]) };
+        assert_eq!(Boolean::from_structure(tag), Err(Error::UnexpectedTag {
+            expected: (TagClass::Universal, TagStructure::Primitive, 1),
+            found: (TagClass::Universal, TagStructure::Primitive, 2),
+        }));
+
+        let tag = StructureTag { class: TagClass::Universal, id: 16, payload: PL::P(vec![]) };
+        assert_eq!(Sequence::from_structure(tag), Err(Error::UnexpectedTag {
+            expected: (TagClass::Universal, TagStructure::Constructed, 16),
+            found: (TagClass::Universal, TagStructure::Primitive, 16),
+        }));
+
+        let tag = StructureTag { class: TagClass::Universal, id: 1, payload: PL::P(vec![0x01, 0x00]) };
+        assert_eq!(Boolean::from_structure(tag), Err(Error::InvalidContent { offset: 0 }));
+
+        let tag = StructureTag { class: TagClass::Universal, id: 2, payload: PL::P(vec![]) };
+        assert_eq!(Integer::from_structure(tag), Err(Error::InvalidContent { offset: 0 }));
+
+        let tag = StructureTag { class: TagClass::Universal, id: 2, payload: PL::P(vec![0x01; 9]) };
+        assert_eq!(Integer::from_structure(tag), Err(Error::Overflow { offset: 0 }));
+
+        let tag = StructureTag { class: TagClass::Universal, id: 5, payload: PL::P(vec![0x00]) };
+        assert_eq!(Null::from_structure(tag), Err(Error::InvalidContent { offset: 0 }));
+    }
+
+    #[test]
+    fn lengths_without_converting() {
+        let time = ::std::time::UNIX_EPOCH + ::std::time::Duration::new(1_000_000_000, 5_000_000);
+        let nested = StructureTag { class: TagClass::Context, id: 40, payload: PL::C(vec![
+            StructureTag { class: TagClass::Universal, id: 4, payload: PL::P(vec![0x61; 200]) },
+        ])};
+        let tags = vec![
+            Tag::Integer(Integer { inner: -129, .. Default::default() }),
+            Tag::BigInteger(BigInteger { inner: vec![0xFF, 0xFF, 0x80], .. Default::default() }),
+            Tag::BigInteger(BigInteger { inner: vec![], .. Default::default() }),
+            Tag::Enumerated(Enumerated { inner: 0, .. Default::default() }),
+            Tag::Boolean(Boolean { inner: true, .. Default::default() }),
+            Tag::Null(Null { .. Default::default() }),
+            Tag::Real(Real { inner: -1.5, .. Default::default() }),
+            Tag::Real(Real { inner: 0.0, .. Default::default() }),
+            Tag::OctetString(OctetString { inner: vec![0x61; 300], id: 31, .. Default::default() }),
+            Tag::BitString(BitString::new(vec![0xF0], 4).unwrap()),
+            Tag::ObjectIdentifier(ObjectIdentifier::new(vec![2, 999, u64::MAX]).unwrap()),
+            Tag::RelativeOid(RelativeOid::new(vec![0, 128]).unwrap()),
+            Tag::Utf8String(Utf8String::new("gr\u{fc}n").unwrap()),
+            Tag::T61String(T61String::new("gr\u{fc}n").unwrap()),
+            Tag::BmpString(BmpString::new("a\u{20ac}").unwrap()),
+            Tag::UniversalString(UniversalString::new("\u{1f600}").unwrap()),
+            Tag::UtcTime(UtcTime::new(time).unwrap()),
+            Tag::GeneralizedTime(GeneralizedTime::new(time).unwrap()),
+            Tag::ExplicitTag(ExplicitTag { class: TagClass::Context, id: 0, inner: Box::new(Tag::Null(Null::default())) }),
+            Tag::ImplicitTag(ImplicitTag::new(TagClass::Context, 1, Box::new(Tag::StructureTag(nested.clone())))),
+            Tag::StructureTag(nested),
+        ];
+        let tags = vec![
+            Tag::Sequence(Sequence { inner: tags.clone(), .. Default::default() }),
+            Tag::Set(Set { inner: tags.clone(), .. Default::default() }),
+            Tag::SequenceOf(SequenceOf::from(tags.clone())),
+            Tag::SetOf(SetOf::from(tags.clone())),
+        ].into_iter().chain(tags);
+
+        for tag in tags {
+            let converted = tag.clone().into_structure();
+            assert_eq!(tag.class_and_id(), (converted.class, converted.id));
+            assert_eq!(ASNTag::structure(&tag), converted.structure());
+            assert_eq!(tag.content_len(), write::content_len(&converted));
+            assert_eq!(ASNTag::encoded_len(&tag), write::encoded_len(&converted));
+        }
+    }
+
+    #[test]
+    fn provided_lengths() {
+        // A type from outside the crate, which only implements `into_structure`.
+        #[derive(Clone)]
+        struct Pair(i64, i64);
+
+        fn integer(inner: i64) -> Tag {
+            Tag::Integer(Integer { inner, .. Default::default() })
+        }
+
+        impl ASNTag for Pair {
+            fn into_structure(self) -> StructureTag {
+                Sequence { inner: vec![integer(self.0), integer(self.1)], .. Default::default() }
+                    .into_structure()
+            }
+        }
+
+        let pair = Pair(5, -129);
+        assert_eq!(pair.class_and_id(), (TagClass::Universal, 16));
+        assert_eq!(ASNTag::structure(&pair), TagStructure::Constructed);
+        assert_eq!(pair.content_len(), 3 + 4);
+        assert_eq!(ASNTag::encoded_len(&pair), 2 + 3 + 4);
+
+        let seq = SequenceOf::from(vec![pair.clone(), pair]);
+        assert_eq!(ASNTag::encoded_len(&seq), 2 + 2 * 9);
+    }
+}
@@ -2,8 +2,9 @@ use std::default;
 use universal;
 use structure;
 
-use super::ASNTag;
-use common::TagClass;
+use super::{ASNTag, FromStructure, expect_universal, expect_structure};
+use common::{TagClass, TagStructure};
+use error::Error;
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct Null {
@@ -20,6 +21,36 @@ impl ASNTag for Null {
             payload: structure::PL::P(Vec::new()),
         }
     }
+
+    fn class_and_id(&self) -> (TagClass, u64) {
+        (self.class, self.id)
+    }
+
+    fn structure(&self) -> TagStructure {
+        TagStructure::Primitive
+    }
+
+    fn content_len(&self) -> usize {
+        0
+    }
+}
+
+impl FromStructure for Null {
+    fn from_structure(tag: structure::StructureTag) -> Result<Self, Error> {
+        expect_universal(&tag, universal::Types::Null, TagStructure::Primitive)?;
+        Self::from_implicit_structure(tag)
+    }
+
+    fn from_implicit_structure(tag: structure::StructureTag) -> Result<Self, Error> {
+        expect_structure(&tag, TagStructure::Primitive)?;
+        let (id, class) = (tag.id, tag.class);
+
+        if !tag.expect_primitive()?.is_empty() {
+            return Err(Error::InvalidContent { offset: 0 });
+        }
+
+        Ok(Null { id, class, inner: () })
+    }
 }
 
 impl default::Default for Null {
@@ -2,8 +2,9 @@ use std::default;
 use universal;
 use structure;
 
-use super::ASNTag;
-use common::TagClass;
+use super::{ASNTag, FromStructure, cer_string, expect_universal, string_content};
+use common::{TagClass, TagStructure};
+use error::Error;
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct OctetString {
@@ -20,6 +21,37 @@ impl ASNTag for OctetString {
             payload: structure::PL::P(self.inner),
         }
     }
+
+    fn class_and_id(&self) -> (TagClass, u64) {
+        (self.class, self.id)
+    }
+
+    fn structure(&self) -> TagStructure {
+        TagStructure::Primitive
+    }
+
+    fn content_len(&self) -> usize {
+        self.inner.len()
+    }
+
+    fn into_cer_structure(self) -> structure::StructureTag {
+        cer_string(self.class, self.id, self.inner, false)
+    }
+}
+
+impl FromStructure for OctetString {
+    fn from_structure(tag: structure::StructureTag) -> Result<Self, Error> {
+        expect_universal(&tag, universal::Types::OctetString, tag.structure())?;
+        Self::from_implicit_structure(tag)
+    }
+
+    /// Segmented (constructed) strings are joined back into one.
+    fn from_implicit_structure(tag: structure::StructureTag) -> Result<Self, Error> {
+        let (id, class) = (tag.id, tag.class);
+        let inner = string_content(tag, universal::Types::OctetString)?;
+
+        Ok(OctetString { id, class, inner })
+    }
 }
 
 impl default::Default for OctetString {
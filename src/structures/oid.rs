@@ -0,0 +1,325 @@
+use std::fmt;
+use std::str::FromStr;
+use universal;
+use structure;
+
+use super::{ASNTag, FromStructure, expect_universal, expect_structure};
+use common::{TagClass, TagStructure};
+use error::Error;
+
+/// An OBJECT IDENTIFIER, kept as its list of arcs.
+///
+/// A valid identifier has at least two arcs, a first arc of 0, 1 or 2 and, below 0 and 1, a
+/// second arc smaller than 40. `new` and `from_str` enforce this; a value built by hand that
+/// breaks these rules does not encode to anything meaningful.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ObjectIdentifier {
+    pub id: u64,
+    pub class: TagClass,
+    pub inner: Vec<u64>,
+}
+
+/// A RELATIVE-OID, the arcs of an object identifier below some known root.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RelativeOid {
+    pub id: u64,
+    pub class: TagClass,
+    pub inner: Vec<u64>,
+}
+
+impl ObjectIdentifier {
+    /// Create a universal OBJECT IDENTIFIER, checking that the arcs form a valid one.
+    pub fn new(arcs: Vec<u64>) -> Result<ObjectIdentifier, Error> {
+        if arcs.len() < 2 || arcs[0] > 2 {
+            return Err(Error::InvalidContent { offset: 0 });
+        }
+        if arcs[0] < 2 && arcs[1] >= 40 {
+            return Err(Error::InvalidContent { offset: 1 });
+        }
+
+        Ok(ObjectIdentifier {
+            id: universal::Types::ObjectIdentifier as u64,
+            class: TagClass::Universal,
+            inner: arcs,
+        })
+    }
+
+    /// The values of the subidentifiers the arcs are encoded as. The first two arcs share one.
+    fn subidentifiers<'a>(&'a self) -> impl Iterator<Item = u128> + 'a {
+        let first = self.inner.first().map(|&first| {
+            first as u128 * 40 + self.inner.get(1).cloned().unwrap_or(0) as u128
+        });
+        first.into_iter().chain(self.inner.iter().skip(2).map(|&arc| arc as u128))
+    }
+}
+
+impl RelativeOid {
+    /// Create a universal RELATIVE-OID.
+    pub fn new(arcs: Vec<u64>) -> Result<RelativeOid, Error> {
+        if arcs.is_empty() {
+            return Err(Error::InvalidContent { offset: 0 });
+        }
+
+        Ok(RelativeOid {
+            id: universal::Types::RelativeOid as u64,
+            class: TagClass::Universal,
+            inner: arcs,
+        })
+    }
+}
+
+/// Number of base 128 groups `value` takes up as a subidentifier.
+fn subidentifier_len(value: u128) -> usize {
+    let mut groups = 1;
+    while groups < 19 && value >> (7 * groups) > 0 {
+        groups += 1;
+    }
+    groups
+}
+
+/// Append one subidentifier in base 128, most significant group first.
+fn encode_subidentifier(out: &mut Vec<u8>, value: u128) {
+    for n in (0..subidentifier_len(value)).rev() {
+        let byte = ((value >> (7 * n)) & 0x7F) as u8;
+        out.push(if n > 0 { byte | 0x80 } else { byte });
+    }
+}
+
+/// Split content octets into subidentifiers. The first one has to fit into `first_limit`, all
+/// others into an u64.
+fn decode_subidentifiers(v: &[u8], first_limit: u128) -> Result<Vec<u128>, Error> {
+    if v.is_empty() {
+        return Err(Error::InvalidContent { offset: 0 });
+    }
+
+    let mut out = Vec::new();
+    let mut value: u128 = 0;
+    let mut start = 0;
+    for (n, &byte) in v.iter().enumerate() {
+        if n == start && byte == 0x80 {
+            return Err(Error::NonMinimalEncoding { offset: n });
+        }
+
+        value = (value << 7) | (byte & 0x7F) as u128;
+        let limit = if out.is_empty() { first_limit } else { u64::MAX as u128 };
+        if value > limit {
+            return Err(Error::Overflow { offset: start });
+        }
+
+        if byte & 0x80 == 0 {
+            out.push(value);
+            value = 0;
+            start = n + 1;
+        }
+    }
+
+    // The last subidentifier ran into the end of the content.
+    if start != v.len() {
+        return Err(Error::InvalidContent { offset: start });
+    }
+
+    Ok(out)
+}
+
+/// Parse a dotted string of arcs, as in "1.2.840.113549".
+fn parse_arcs(s: &str) -> Result<Vec<u64>, Error> {
+    let mut arcs = Vec::new();
+    let mut offset = 0;
+    for part in s.split('.') {
+        if part.is_empty() || !part.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(Error::InvalidContent { offset });
+        }
+        arcs.push(part.parse::<u64>().map_err(|_| Error::Overflow { offset })?);
+        offset += part.len() + 1;
+    }
+    Ok(arcs)
+}
+
+fn fmt_arcs(arcs: &[u64], f: &mut fmt::Formatter) -> fmt::Result {
+    for (n, arc) in arcs.iter().enumerate() {
+        if n > 0 {
+            write!(f, ".")?;
+        }
+        write!(f, "{}", arc)?;
+    }
+    Ok(())
+}
+
+impl ASNTag for ObjectIdentifier {
+    fn into_structure(self) -> structure::StructureTag {
+        let mut out = Vec::new();
+        for subid in self.subidentifiers() {
+            encode_subidentifier(&mut out, subid);
+        }
+
+        structure::StructureTag {
+            id: self.id,
+            class: self.class,
+            payload: structure::PL::P(out),
+        }
+    }
+
+    fn class_and_id(&self) -> (TagClass, u64) {
+        (self.class, self.id)
+    }
+
+    fn structure(&self) -> TagStructure {
+        TagStructure::Primitive
+    }
+
+    fn content_len(&self) -> usize {
+        self.subidentifiers().map(subidentifier_len).sum()
+    }
+}
+
+impl FromStructure for ObjectIdentifier {
+    fn from_structure(tag: structure::StructureTag) -> Result<Self, Error> {
+        expect_universal(&tag, universal::Types::ObjectIdentifier, TagStructure::Primitive)?;
+        Self::from_implicit_structure(tag)
+    }
+
+    fn from_implicit_structure(tag: structure::StructureTag) -> Result<Self, Error> {
+        expect_structure(&tag, TagStructure::Primitive)?;
+        let (id, class) = (tag.id, tag.class);
+
+        // Under arc 2 the second arc may be as large as any other, pushing the first subidentifier
+        // past an u64.
+        let subids = decode_subidentifiers(&tag.expect_primitive()?, u64::MAX as u128 + 80)?;
+        let mut inner = Vec::with_capacity(subids.len() + 1);
+        match subids[0] {
+            x if x < 40 => { inner.push(0); inner.push(x as u64); },
+            x if x < 80 => { inner.push(1); inner.push((x - 40) as u64); },
+            x => { inner.push(2); inner.push((x - 80) as u64); },
+        }
+        inner.extend(subids[1..].iter().map(|&x| x as u64));
+
+        Ok(ObjectIdentifier { id, class, inner })
+    }
+}
+
+impl fmt::Display for ObjectIdentifier {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt_arcs(&self.inner, f)
+    }
+}
+
+impl FromStr for ObjectIdentifier {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<ObjectIdentifier, Error> {
+        ObjectIdentifier::new(parse_arcs(s)?)
+    }
+}
+
+impl ASNTag for RelativeOid {
+    fn into_structure(self) -> structure::StructureTag {
+        let mut out = Vec::new();
+        for &arc in self.inner.iter() {
+            encode_subidentifier(&mut out, arc as u128);
+        }
+
+        structure::StructureTag {
+            id: self.id,
+            class: self.class,
+            payload: structure::PL::P(out),
+        }
+    }
+
+    fn class_and_id(&self) -> (TagClass, u64) {
+        (self.class, self.id)
+    }
+
+    fn structure(&self) -> TagStructure {
+        TagStructure::Primitive
+    }
+
+    fn content_len(&self) -> usize {
+        self.inner.iter().map(|&arc| subidentifier_len(arc as u128)).sum()
+    }
+}
+
+impl FromStructure for RelativeOid {
+    fn from_structure(tag: structure::StructureTag) -> Result<Self, Error> {
+        expect_universal(&tag, universal::Types::RelativeOid, TagStructure::Primitive)?;
+        Self::from_implicit_structure(tag)
+    }
+
+    fn from_implicit_structure(tag: structure::StructureTag) -> Result<Self, Error> {
+        expect_structure(&tag, TagStructure::Primitive)?;
+        let (id, class) = (tag.id, tag.class);
+
+        let subids = decode_subidentifiers(&tag.expect_primitive()?, u64::MAX as u128)?;
+        let inner = subids.into_iter().map(|x| x as u64).collect();
+
+        Ok(RelativeOid { id, class, inner })
+    }
+}
+
+impl fmt::Display for RelativeOid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt_arcs(&self.inner, f)
+    }
+}
+
+impl FromStr for RelativeOid {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<RelativeOid, Error> {
+        RelativeOid::new(parse_arcs(s)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use structure::{StructureTag, PL};
+
+    fn oid_tag(content: Vec<u8>) -> StructureTag {
+        StructureTag { class: TagClass::Universal, id: 6, payload: PL::P(content) }
+    }
+
+    #[test]
+    fn encode_oid() {
+        let oid: ObjectIdentifier = "1.2.840.113549.1.1.11".parse().unwrap();
+        assert_eq!(oid.clone().into_structure(),
+                   oid_tag(vec![0x2A, 0x86, 0x48, 0x86, 0xF7, 0x0D, 0x01, 0x01, 0x0B]));
+        assert_eq!(ObjectIdentifier::from_structure(oid.clone().into_structure()), Ok(oid.clone()));
+        assert_eq!(oid.to_string(), "1.2.840.113549.1.1.11");
+
+        // Under arc 2 the second arc may exceed 39, and even 64 bits.
+        let oid: ObjectIdentifier = "2.999.3".parse().unwrap();
+        assert_eq!(oid.clone().into_structure(), oid_tag(vec![0x88, 0x37, 0x03]));
+        assert_eq!(ObjectIdentifier::from_structure(oid.into_structure()).unwrap().inner, vec![2, 999, 3]);
+
+        let oid = ObjectIdentifier::new(vec![2, u64::MAX]).unwrap();
+        assert_eq!(ObjectIdentifier::from_structure(oid.clone().into_structure()), Ok(oid));
+    }
+
+    #[test]
+    fn invalid_oid() {
+        assert_eq!("1.40".parse::<ObjectIdentifier>(), Err(Error::InvalidContent { offset: 1 }));
+        assert_eq!("3.1".parse::<ObjectIdentifier>(), Err(Error::InvalidContent { offset: 0 }));
+        assert_eq!("1".parse::<ObjectIdentifier>(), Err(Error::InvalidContent { offset: 0 }));
+        assert_eq!("1.2..3".parse::<ObjectIdentifier>(), Err(Error::InvalidContent { offset: 4 }));
+        assert_eq!("1.2.18446744073709551616".parse::<ObjectIdentifier>(), Err(Error::Overflow { offset: 4 }));
+
+        assert_eq!(ObjectIdentifier::from_structure(oid_tag(vec![0x2A, 0x80, 0x01])),
+                   Err(Error::NonMinimalEncoding { offset: 1 }));
+        assert_eq!(ObjectIdentifier::from_structure(oid_tag(vec![0x2A, 0x86])),
+                   Err(Error::InvalidContent { offset: 1 }));
+        assert_eq!(ObjectIdentifier::from_structure(oid_tag(vec![])),
+                   Err(Error::InvalidContent { offset: 0 }));
+        assert_eq!(ObjectIdentifier::from_structure(oid_tag(vec![0x2A, 0x82, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x00])),
+                   Err(Error::Overflow { offset: 1 }));
+    }
+
+    #[test]
+    fn relative_oid() {
+        let oid: RelativeOid = "8571.3.2".parse().unwrap();
+        let tag = oid.clone().into_structure();
+        assert_eq!(tag, StructureTag { class: TagClass::Universal, id: 13, payload: PL::P(vec![0xC2, 0x7B, 0x03, 0x02]) });
+        assert_eq!(RelativeOid::from_structure(tag), Ok(oid.clone()));
+        assert_eq!(oid.to_string(), "8571.3.2");
+        assert!("".parse::<RelativeOid>().is_err());
+    }
+}
@@ -0,0 +1,428 @@
+use common::{TagClass, TagStructure};
+use error::Error;
+use super::{ASNTag, FromStructure, expect_universal, expect_structure};
+use super::integer::{decode_i64, encode_i64};
+use universal;
+use structure;
+use write;
+
+use std::default;
+
+const PLUS_INFINITY: u8 = 0x40;
+const MINUS_INFINITY: u8 = 0x41;
+const NOT_A_NUMBER: u8 = 0x42;
+const MINUS_ZERO: u8 = 0x43;
+
+/// A REAL.
+///
+/// Values are encoded in the binary form with base 2, which is exact for every `f64` and the
+/// form DER requires. All binary and decimal forms BER allows are decoded.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Real {
+    pub id: u64,
+    pub class: TagClass,
+    pub inner: f64,
+}
+
+impl Real {
+    /// Encode yourself in the decimal NR3 form DER allows, as in "314.E-2".
+    pub fn into_decimal_structure(self) -> structure::StructureTag {
+        let content = match special_value(self.inner) {
+            Some(content) => content,
+            None => {
+                // The shortest digits that read back as the same f64.
+                let formatted = format!("{:e}", self.inner.abs());
+                let (mantissa, exponent) = formatted.split_at(formatted.find('e').unwrap());
+                let mut digits: String = mantissa.chars().filter(|&c| c != '.').collect();
+                let mut exponent = exponent[1..].parse::<i64>().unwrap() - (digits.len() as i64 - 1);
+                while digits.len() > 1 && digits.ends_with('0') {
+                    digits.pop();
+                    exponent += 1;
+                }
+
+                let sign = if self.inner < 0.0 { "-" } else { "" };
+                let exponent = if exponent == 0 { "+0".to_string() } else { exponent.to_string() };
+                let mut out = vec![0x03];
+                out.extend(format!("{}{}.E{}", sign, digits, exponent).into_bytes());
+                out
+            }
+        };
+
+        structure::StructureTag {
+            id: self.id,
+            class: self.class,
+            payload: structure::PL::P(content),
+        }
+    }
+
+    /// The content octets in the binary form `into_structure` uses.
+    fn binary_content(&self) -> Vec<u8> {
+        special_value(self.inner).unwrap_or_else(|| {
+            let bits = self.inner.to_bits();
+            let biased = ((bits >> 52) & 0x7FF) as i64;
+            let fraction = bits & ((1 << 52) - 1);
+            let (mut m, mut e) = if biased == 0 {
+                (fraction, -1074)
+            } else {
+                (fraction | (1 << 52), biased - 1075)
+            };
+
+            // An odd mantissa, as DER wants it.
+            e += m.trailing_zeros() as i64;
+            m >>= m.trailing_zeros();
+
+            let e = encode_i64(e);
+            let m = m.to_be_bytes();
+            let m = &m[m.iter().take_while(|&&b| b == 0).count()..];
+
+            let sign = if self.inner < 0.0 { 0x40 } else { 0x00 };
+            let mut out = vec![0x80 | sign | (e.len() as u8 - 1)];
+            out.extend_from_slice(&e);
+            out.extend_from_slice(m);
+            out
+        })
+    }
+}
+
+/// The content octets of zero, the infinities and NaN, which have no mantissa and exponent.
+fn special_value(value: f64) -> Option<Vec<u8>> {
+    if value.is_nan() {
+        Some(vec![NOT_A_NUMBER])
+    } else if value == f64::INFINITY {
+        Some(vec![PLUS_INFINITY])
+    } else if value == f64::NEG_INFINITY {
+        Some(vec![MINUS_INFINITY])
+    } else if value == 0.0 && value.is_sign_negative() {
+        Some(vec![MINUS_ZERO])
+    } else if value == 0.0 {
+        Some(vec![])
+    } else {
+        None
+    }
+}
+
+/// `m * 2^e`, rounded once if the result is subnormal, or `None` if it is too large for an f64.
+fn scale(m: u64, e: i128) -> Option<f64> {
+    let pow2 = |e: i128| f64::from_bits(((e + 1023) as u64) << 52);
+
+    // Split `m` into a mantissa between 1 and 2 and a power of two.
+    let x = m as f64;
+    let e = e + ((x.to_bits() >> 52) as i128 - 1023);
+    let x = f64::from_bits((x.to_bits() & !(0x7FF << 52)) | (1023 << 52));
+
+    if e > 1023 {
+        None
+    } else if e >= -1022 {
+        Some(x * pow2(e))
+    } else if e >= -1080 {
+        Some(x * pow2(e + 64) * pow2(-64))
+    } else {
+        Some(0.0)
+    }
+}
+
+fn decode_binary(v: &[u8]) -> Result<f64, Error> {
+    let first = v[0];
+    let digit_bits = match (first >> 4) & 0x03 {
+        0 => 1,
+        1 => 3,
+        2 => 4,
+        _ => return Err(Error::InvalidContent { offset: 0 }),
+    };
+    let scale_factor = (first >> 2) & 0x03;
+
+    let (start, len) = match first & 0x03 {
+        3 => match v.get(1) {
+            Some(&len) if len > 0 => (2, len as usize),
+            _ => return Err(Error::InvalidContent { offset: 1 }),
+        },
+        n => (1, n as usize + 1),
+    };
+    if v.len() <= start + len {
+        return Err(Error::InvalidContent { offset: v.len() });
+    }
+    let exponent = decode_i64(&v[start..start + len]).map_err(|e| e.shift(start))?;
+
+    // Leading zero octets don't matter; trailing zero bits move into the exponent.
+    let n = &v[start + len..];
+    let n = &n[n.iter().take_while(|&&b| b == 0).count()..];
+    let mut e = exponent as i128 * digit_bits + scale_factor as i128;
+    let value = match n.iter().rposition(|&b| b != 0) {
+        None => 0.0,
+        Some(last) => {
+            e += 8 * (n.len() - 1 - last) as i128;
+            let n = &n[..last + 1];
+
+            // More than 64 bits can't be exact anyway. The dropped octets only decide rounding,
+            // so they collapse into one sticky bit.
+            let kept = n.len().min(8);
+            let mut m = n[..kept].iter().fold(0u64, |acc, &b| (acc << 8) | b as u64);
+            if n[kept..].iter().any(|&b| b != 0) {
+                m |= 1;
+            }
+            e += 8 * (n.len() - kept) as i128;
+
+            scale(m, e).ok_or(Error::Overflow { offset: start + len })?
+        }
+    };
+
+    Ok(if first & 0x40 != 0 { -value } else { value })
+}
+
+/// Decode the ISO 6093 number forms NR1 (integer), NR2 (decimal fraction) and NR3 (with
+/// exponent), which may start with spaces and use a comma as the decimal mark.
+fn decode_decimal(v: &[u8]) -> Result<f64, Error> {
+    let form = v[0] & 0x3F;
+    let start = 1 + v[1..].iter().take_while(|&&b| b == b' ').count();
+    let text = &v[start..];
+
+    let digits = |s: &[u8]| !s.is_empty() && s.iter().all(|b| b.is_ascii_digit());
+    fn unsigned(s: &[u8]) -> &[u8] {
+        match s.first() {
+            Some(&b'+') | Some(&b'-') => &s[1..],
+            _ => s,
+        }
+    }
+    let fraction = |s: &[u8]| match s.iter().position(|&b| b == b'.' || b == b',') {
+        Some(n) => (n > 0 || s.len() > 1)
+            && (n == 0 || digits(&s[..n])) && (n + 1 == s.len() || digits(&s[n + 1..])),
+        None => false,
+    };
+
+    let valid = match form {
+        1 => digits(unsigned(text)),
+        2 => fraction(unsigned(text)),
+        3 => match text.iter().position(|&b| b == b'E' || b == b'e') {
+            Some(n) => {
+                let mantissa = unsigned(&text[..n]);
+                (digits(mantissa) || fraction(mantissa)) && digits(unsigned(&text[n + 1..]))
+            },
+            None => false,
+        },
+        _ => return Err(Error::InvalidContent { offset: 0 }),
+    };
+    if !valid {
+        return Err(Error::InvalidContent { offset: start });
+    }
+
+    // What's left is plain ASCII that Rust parses with correct rounding.
+    let text: String = text.iter().map(|&b| if b == b',' { '.' } else { b as char }).collect();
+    let value = text.parse::<f64>().map_err(|_| Error::InvalidContent { offset: start })?;
+    if value.is_infinite() {
+        return Err(Error::Overflow { offset: start });
+    }
+    Ok(value)
+}
+
+/// Check that the content of a REAL is in the form DER and CER require: binary with base 2, a
+/// scale factor of 0, an odd mantissa and a minimal exponent, or decimal in the NR3 form
+/// without redundant zeros.
+pub(crate) fn check_canonical(v: &[u8]) -> Result<(), Error> {
+    let first = match v.first() {
+        Some(&first) => first,
+        None => return Ok(()),
+    };
+
+    if first & 0x80 != 0 {
+        let len = (first & 0x03) as usize + 1;
+        if first & 0x3C != 0 || first & 0x03 == 3 {
+            return Err(Error::NonCanonical { offset: 0 });
+        }
+        // An exponent without mantissa octets doesn't decode at all.
+        if v.len() <= len + 1 {
+            return Err(Error::InvalidContent { offset: v.len() });
+        }
+        if write::redundant_int_octets(&v[1..len + 1]) > 0 || v[len + 1] == 0 || v[v.len() - 1] & 1 == 0 {
+            return Err(Error::NonCanonical { offset: 1 });
+        }
+    } else if first & 0x40 == 0 {
+        // "-" digits without leading or trailing zeros "." "E" and "+0" or a non-zero exponent.
+        let text = &v[1..];
+        let text = if text.first() == Some(&b'-') { &text[1..] } else { text };
+        let mark = text.iter().position(|&b| b == b'.').unwrap_or(0);
+        let exponent = if text.len() > mark + 2 { &text[mark + 2..] } else { &[][..] };
+        let exponent = if exponent.first() == Some(&b'-') { &exponent[1..] } else { exponent };
+        let canonical = first == 0x03
+            && mark > 0 && text[..mark].iter().all(|b| b.is_ascii_digit())
+            && text[0] != b'0' && text[mark - 1] != b'0'
+            && text.get(mark + 1) == Some(&b'E')
+            && (exponent == b"+0"
+                || (!exponent.is_empty() && exponent[0] != b'0' && exponent.iter().all(|b| b.is_ascii_digit())));
+        if !canonical {
+            return Err(Error::NonCanonical { offset: 0 });
+        }
+    }
+
+    Ok(())
+}
+
+impl ASNTag for Real {
+    fn into_structure(self) -> structure::StructureTag {
+        structure::StructureTag {
+            id: self.id,
+            class: self.class,
+            payload: structure::PL::P(self.binary_content()),
+        }
+    }
+
+    fn class_and_id(&self) -> (TagClass, u64) {
+        (self.class, self.id)
+    }
+
+    fn structure(&self) -> TagStructure {
+        TagStructure::Primitive
+    }
+
+    fn content_len(&self) -> usize {
+        self.binary_content().len()
+    }
+}
+
+impl FromStructure for Real {
+    fn from_structure(tag: structure::StructureTag) -> Result<Self, Error> {
+        expect_universal(&tag, universal::Types::Real, TagStructure::Primitive)?;
+        Self::from_implicit_structure(tag)
+    }
+
+    fn from_implicit_structure(tag: structure::StructureTag) -> Result<Self, Error> {
+        expect_structure(&tag, TagStructure::Primitive)?;
+        let (id, class) = (tag.id, tag.class);
+        let v = tag.expect_primitive()?;
+
+        let inner = match v.first() {
+            None => 0.0,
+            Some(&first) if first & 0x80 != 0 => decode_binary(&v)?,
+            Some(&first) if first & 0x40 == 0 => decode_decimal(&v)?,
+            Some(_) if v.len() > 1 => return Err(Error::InvalidContent { offset: 1 }),
+            Some(&PLUS_INFINITY) => f64::INFINITY,
+            Some(&MINUS_INFINITY) => f64::NEG_INFINITY,
+            Some(&NOT_A_NUMBER) => f64::NAN,
+            Some(&MINUS_ZERO) => -0.0,
+            Some(_) => return Err(Error::InvalidContent { offset: 0 }),
+        };
+
+        Ok(Real { id, class, inner })
+    }
+}
+
+impl default::Default for Real {
+    fn default() -> Self {
+        Real {
+            id: universal::Types::Real as u64,
+            class: TagClass::Universal,
+            inner: 0.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use structure::{StructureTag, PL};
+
+    fn real_tag(content: Vec<u8>) -> StructureTag {
+        StructureTag { class: TagClass::Universal, id: 9, payload: PL::P(content) }
+    }
+
+    fn decode(content: Vec<u8>) -> Result<f64, Error> {
+        Real::from_structure(real_tag(content)).map(|r| r.inner)
+    }
+
+    fn real(inner: f64) -> Real {
+        Real { inner, .. Default::default() }
+    }
+
+    #[test]
+    fn encode_binary() {
+        let cases: Vec<(f64, Vec<u8>)> = vec![
+            (1.0, vec![0x80, 0x00, 0x01]),
+            (0.5, vec![0x80, 0xFF, 0x01]),
+            (-3.0, vec![0xC0, 0x00, 0x03]),
+            (1024.0, vec![0x80, 0x0A, 0x01]),
+            (f64::MAX, vec![0x81, 0x03, 0xCB, 0x1F, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]),
+            (5e-324, vec![0x81, 0xFB, 0xCE, 0x01]),
+        ];
+        for (value, content) in cases {
+            assert_eq!(real(value).into_structure(), real_tag(content.clone()));
+            assert_eq!(decode(content.clone()), Ok(value));
+            assert_eq!(check_canonical(&content), Ok(()));
+        }
+
+        let value = 0.1f64;
+        assert_eq!(decode(real(value).into_structure().expect_primitive().unwrap()), Ok(value));
+    }
+
+    #[test]
+    fn special_values() {
+        assert_eq!(real(0.0).into_structure(), real_tag(vec![]));
+        assert_eq!(real(-0.0).into_structure(), real_tag(vec![0x43]));
+        assert_eq!(real(f64::INFINITY).into_structure(), real_tag(vec![0x40]));
+        assert_eq!(real(f64::NEG_INFINITY).into_structure(), real_tag(vec![0x41]));
+        assert_eq!(real(f64::NAN).into_structure(), real_tag(vec![0x42]));
+
+        assert!(decode(vec![]).unwrap().is_sign_positive());
+        assert!(decode(vec![0x43]).unwrap().is_sign_negative());
+        assert!(decode(vec![0x42]).unwrap().is_nan());
+        assert_eq!(decode(vec![0x41]), Ok(f64::NEG_INFINITY));
+        assert_eq!(decode(vec![0x44]), Err(Error::InvalidContent { offset: 0 }));
+        assert_eq!(decode(vec![0x40, 0x00]), Err(Error::InvalidContent { offset: 1 }));
+    }
+
+    #[test]
+    fn decode_binary_forms() {
+        // 3 * 8^1, base 8
+        assert_eq!(decode(vec![0x90, 0x01, 0x03]), Ok(24.0));
+        // 1 * 16^-1 * 2^2, base 16 with a scale factor
+        assert_eq!(decode(vec![0xA8, 0xFF, 0x01]), Ok(0.25));
+        // Two octet exponent, even mantissa with leading and trailing zero octets
+        assert_eq!(decode(vec![0x81, 0x00, 0x01, 0x00, 0x06, 0x00]), Ok(3072.0));
+        // Exponent with its own length octet
+        assert_eq!(decode(vec![0x83, 0x01, 0xFE, 0x05]), Ok(1.25));
+        // Mantissa wider than 64 bits, rounded
+        assert_eq!(decode(vec![0x80, 0x00, 0x01, 0, 0, 0, 0, 0, 0, 0, 0x01]), Ok(18446744073709551616.0));
+
+        assert_eq!(decode(vec![0xB0, 0x00, 0x01]), Err(Error::InvalidContent { offset: 0 }));
+        assert_eq!(decode(vec![0x81, 0x00, 0x01]), Err(Error::InvalidContent { offset: 3 }));
+        assert_eq!(decode(vec![0x83, 0x00, 0x01]), Err(Error::InvalidContent { offset: 1 }));
+        assert_eq!(decode(vec![0x81, 0x10, 0x00, 0x01]), Err(Error::Overflow { offset: 3 }));
+        assert_eq!(decode(vec![0x83, 0x09, 0x01, 0, 0, 0, 0, 0, 0, 0, 0, 0x01]), Err(Error::Overflow { offset: 2 }));
+        assert_eq!(decode(vec![0x81, 0xF0, 0x00, 0x01]), Ok(0.0));
+    }
+
+    #[test]
+    fn decimal_forms() {
+        assert_eq!(decode(b"\x01  -42".to_vec()), Ok(-42.0));
+        assert_eq!(decode(b"\x02 3,25".to_vec()), Ok(3.25));
+        assert_eq!(decode(b"\x02-.5".to_vec()), Ok(-0.5));
+        assert_eq!(decode(b"\x03325.E-2".to_vec()), Ok(3.25));
+        assert_eq!(decode(b"\x03+1,5e+3".to_vec()), Ok(1500.0));
+
+        assert_eq!(decode(b"\x011.5".to_vec()), Err(Error::InvalidContent { offset: 1 }));
+        assert_eq!(decode(b"\x0215".to_vec()), Err(Error::InvalidContent { offset: 1 }));
+        assert_eq!(decode(b"\x031.5".to_vec()), Err(Error::InvalidContent { offset: 1 }));
+        assert_eq!(decode(b"\x03inf".to_vec()), Err(Error::InvalidContent { offset: 1 }));
+        assert_eq!(decode(b"\x041".to_vec()), Err(Error::InvalidContent { offset: 0 }));
+        assert_eq!(decode(b"\x031.E400".to_vec()), Err(Error::Overflow { offset: 1 }));
+
+        assert_eq!(real(3.25).into_decimal_structure(), real_tag(b"\x03325.E-2".to_vec()));
+        assert_eq!(real(-1500.0).into_decimal_structure(), real_tag(b"\x03-15.E2".to_vec()));
+        assert_eq!(real(1.0).into_decimal_structure(), real_tag(b"\x031.E+0".to_vec()));
+        assert_eq!(decode(real(1e-300).into_decimal_structure().expect_primitive().unwrap()), Ok(1e-300));
+    }
+
+    #[test]
+    fn canonical_real() {
+        assert_eq!(check_canonical(b"\x03314.E-2"), Ok(()));
+        assert_eq!(check_canonical(b"\x031.E+0"), Ok(()));
+        assert_eq!(check_canonical(&[0x43]), Ok(()));
+
+        assert_eq!(check_canonical(&[0x90, 0x01, 0x03]), Err(Error::NonCanonical { offset: 0 }));
+        assert_eq!(check_canonical(&[0x80, 0x00, 0x02]), Err(Error::NonCanonical { offset: 1 }));
+        assert_eq!(check_canonical(&[0x81, 0x00, 0x01, 0x03]), Err(Error::NonCanonical { offset: 1 }));
+        assert_eq!(check_canonical(&[0x80, 0x00]), Err(Error::InvalidContent { offset: 2 }));
+        assert_eq!(check_canonical(&[0x81, 0x00]), Err(Error::InvalidContent { offset: 2 }));
+        assert_eq!(check_canonical(b"\x023.14"), Err(Error::NonCanonical { offset: 0 }));
+        assert_eq!(check_canonical(b"\x03310.E-2"), Err(Error::NonCanonical { offset: 0 }));
+        assert_eq!(check_canonical(b"\x031.E0"), Err(Error::NonCanonical { offset: 0 }));
+    }
+}
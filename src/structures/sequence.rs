@@ -1,9 +1,13 @@
+use std::cmp::Ordering;
 use std::default;
 use universal;
 use structure;
+use write;
+use parse::Rules;
 
-use super::{ASNTag, Tag};
-use common::TagClass;
+use super::{ASNTag, FromStructure, Tag, expect_universal, expect_structure};
+use common::{TagClass, TagStructure};
+use error::Error;
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct Sequence {
@@ -20,6 +24,46 @@ impl ASNTag for Sequence {
             payload: structure::PL::C(self.inner.into_iter().map(|x| x.into_structure()).collect()),
         }
     }
+
+    fn class_and_id(&self) -> (TagClass, u64) {
+        (self.class, self.id)
+    }
+
+    fn structure(&self) -> TagStructure {
+        TagStructure::Constructed
+    }
+
+    fn into_der_structure(self) -> structure::StructureTag {
+        structure::StructureTag {
+            id: self.id,
+            class: self.class,
+            payload: structure::PL::C(self.inner.into_iter().map(|x| x.into_der_structure()).collect()),
+        }
+    }
+
+    fn into_cer_structure(self) -> structure::StructureTag {
+        structure::StructureTag {
+            id: self.id,
+            class: self.class,
+            payload: structure::PL::C(self.inner.into_iter().map(|x| x.into_cer_structure()).collect()),
+        }
+    }
+}
+
+impl FromStructure for Sequence {
+    fn from_structure(tag: structure::StructureTag) -> Result<Self, Error> {
+        expect_universal(&tag, universal::Types::Sequence, TagStructure::Constructed)?;
+        Self::from_implicit_structure(tag)
+    }
+
+    /// The members are left undecoded, as their types aren't known.
+    fn from_implicit_structure(tag: structure::StructureTag) -> Result<Self, Error> {
+        expect_structure(&tag, TagStructure::Constructed)?;
+        let (id, class) = (tag.id, tag.class);
+        let inner = tag.expect_constructed()?.into_iter().map(Tag::StructureTag).collect();
+
+        Ok(Sequence { id, class, inner })
+    }
 }
 
 impl default::Default for Sequence {
@@ -39,7 +83,7 @@ pub struct SequenceOf<T> {
     pub inner: Vec<T>,
 }
 
-impl<T: ASNTag + Sized> SequenceOf<T> {
+impl<T: ASNTag + Sized> ASNTag for SequenceOf<T> {
     fn into_structure(self) -> structure::StructureTag {
         structure::StructureTag {
             id: self.id,
@@ -47,6 +91,45 @@ impl<T: ASNTag + Sized> SequenceOf<T> {
             payload: structure::PL::C(self.inner.into_iter().map(|x| x.into_structure()).collect()),
         }
     }
+
+    fn class_and_id(&self) -> (TagClass, u64) {
+        (self.class, self.id)
+    }
+
+    fn structure(&self) -> TagStructure {
+        TagStructure::Constructed
+    }
+
+    fn into_der_structure(self) -> structure::StructureTag {
+        structure::StructureTag {
+            id: self.id,
+            class: self.class,
+            payload: structure::PL::C(self.inner.into_iter().map(|x| x.into_der_structure()).collect()),
+        }
+    }
+
+    fn into_cer_structure(self) -> structure::StructureTag {
+        structure::StructureTag {
+            id: self.id,
+            class: self.class,
+            payload: structure::PL::C(self.inner.into_iter().map(|x| x.into_cer_structure()).collect()),
+        }
+    }
+}
+
+impl<T: FromStructure> FromStructure for SequenceOf<T> {
+    fn from_structure(tag: structure::StructureTag) -> Result<Self, Error> {
+        expect_universal(&tag, universal::Types::Sequence, TagStructure::Constructed)?;
+        Self::from_implicit_structure(tag)
+    }
+
+    fn from_implicit_structure(tag: structure::StructureTag) -> Result<Self, Error> {
+        expect_structure(&tag, TagStructure::Constructed)?;
+        let (id, class) = (tag.id, tag.class);
+        let inner = tag.expect_constructed()?.into_iter().map(T::from_structure).collect::<Result<_, _>>()?;
+
+        Ok(SequenceOf { id, class, inner })
+    }
 }
 
 impl<T: ASNTag + Sized> default::Default for SequenceOf<T> {
@@ -59,6 +142,18 @@ impl<T: ASNTag + Sized> default::Default for SequenceOf<T> {
     }
 }
 
+impl<T: ASNTag + Sized> From<Vec<T>> for SequenceOf<T> {
+    fn from(inner: Vec<T>) -> Self {
+        SequenceOf { inner, .. Default::default() }
+    }
+}
+
+impl<T> From<SequenceOf<T>> for Vec<T> {
+    fn from(seq: SequenceOf<T>) -> Vec<T> {
+        seq.inner
+    }
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct SetOf<T> {
     pub id: u64,
@@ -74,8 +169,61 @@ impl<T: ASNTag + Sized> ASNTag for SetOf<T> {
             payload: structure::PL::C(self.inner.into_iter().map(|x| x.into_structure()).collect()),
         }
     }
+
+    fn class_and_id(&self) -> (TagClass, u64) {
+        (self.class, self.id)
+    }
+
+    fn structure(&self) -> TagStructure {
+        TagStructure::Constructed
+    }
+
+    /// DER wants the members of a SET OF sorted by their encoding, whichever way it is tagged.
+    fn into_der_structure(self) -> structure::StructureTag {
+        let members = self.inner.into_iter().map(|x| x.into_der_structure()).collect();
+        sorted_set(self.class, self.id, members, write::set_of_order, Rules::Der)
+    }
+
+    /// Same as for DER, only sorted by the CER encoding.
+    fn into_cer_structure(self) -> structure::StructureTag {
+        let members = self.inner.into_iter().map(|x| x.into_cer_structure()).collect();
+        sorted_set(self.class, self.id, members, write::set_of_order, Rules::Cer)
+    }
 }
 
+/// The order of the members of a SET or SET OF, see `write::set_order` and `write::set_of_order`.
+type Order = fn(&write::SetMember, &write::SetMember) -> Ordering;
+
+/// Build a SET with its members sorted by their encoding under `rules` in `order`.
+fn sorted_set(class: TagClass, id: u64, members: Vec<structure::StructureTag>, order: Order, rules: Rules)
+    -> structure::StructureTag
+{
+    let mut members: Vec<_> = members.into_iter()
+        .map(|tag| ((tag.class, tag.id, write::encode_to_vec(&tag, rules)), tag))
+        .collect();
+    members.sort_by(|a, b| order(&a.0, &b.0));
+
+    structure::StructureTag {
+        id,
+        class,
+        payload: structure::PL::C(members.into_iter().map(|(_, tag)| tag).collect()),
+    }
+}
+
+impl<T: FromStructure> FromStructure for SetOf<T> {
+    fn from_structure(tag: structure::StructureTag) -> Result<Self, Error> {
+        expect_universal(&tag, universal::Types::Set, TagStructure::Constructed)?;
+        Self::from_implicit_structure(tag)
+    }
+
+    fn from_implicit_structure(tag: structure::StructureTag) -> Result<Self, Error> {
+        expect_structure(&tag, TagStructure::Constructed)?;
+        let (id, class) = (tag.id, tag.class);
+        let inner = tag.expect_constructed()?.into_iter().map(T::from_structure).collect::<Result<_, _>>()?;
+
+        Ok(SetOf { id, class, inner })
+    }
+}
 
 impl<T: ASNTag + Sized> default::Default for SetOf<T> {
     fn default() -> Self {
@@ -86,3 +234,102 @@ impl<T: ASNTag + Sized> default::Default for SetOf<T> {
         }
     }
 }
+
+impl<T: ASNTag + Sized> From<Vec<T>> for SetOf<T> {
+    fn from(inner: Vec<T>) -> Self {
+        SetOf { inner, .. Default::default() }
+    }
+}
+
+impl<T> From<SetOf<T>> for Vec<T> {
+    fn from(set: SetOf<T>) -> Vec<T> {
+        set.inner
+    }
+}
+
+/// A SET of members of any type, identified by their tags.
+///
+/// Members are kept in the order they are given or decoded in; DER and CER sort them by tag.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Set {
+    pub id: u64,
+    pub class: TagClass,
+    pub inner: Vec<Tag>,
+}
+
+impl Set {
+    /// The member with the given tag, if there is one.
+    pub fn get(&self, class: TagClass, id: u64) -> Option<&Tag> {
+        self.inner.iter().find(|x| x.class_and_id() == (class, id))
+    }
+
+    /// Remove the member with the given tag and return it.
+    pub fn remove(&mut self, class: TagClass, id: u64) -> Option<Tag> {
+        let pos = self.inner.iter().position(|x| x.class_and_id() == (class, id))?;
+        Some(self.inner.remove(pos))
+    }
+
+    /// Remove the member with the given tag and decode it as `T`, keeping its class and id.
+    pub fn decode<T: FromStructure>(&mut self, class: TagClass, id: u64) -> Result<Option<T>, Error> {
+        match self.remove(class, id) {
+            Some(tag) => T::from_implicit_structure(tag.into_structure()).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+impl ASNTag for Set {
+    fn into_structure(self) -> structure::StructureTag {
+        structure::StructureTag {
+            id: self.id,
+            class: self.class,
+            payload: structure::PL::C(self.inner.into_iter().map(|x| x.into_structure()).collect()),
+        }
+    }
+
+    fn class_and_id(&self) -> (TagClass, u64) {
+        (self.class, self.id)
+    }
+
+    fn structure(&self) -> TagStructure {
+        TagStructure::Constructed
+    }
+
+    /// DER wants the members of a SET sorted by tag, whichever way it is tagged.
+    fn into_der_structure(self) -> structure::StructureTag {
+        let members = self.inner.into_iter().map(|x| x.into_der_structure()).collect();
+        sorted_set(self.class, self.id, members, write::set_order, Rules::Der)
+    }
+
+    /// Same as for DER, only with the members in their CER form.
+    fn into_cer_structure(self) -> structure::StructureTag {
+        let members = self.inner.into_iter().map(|x| x.into_cer_structure()).collect();
+        sorted_set(self.class, self.id, members, write::set_order, Rules::Cer)
+    }
+}
+
+impl FromStructure for Set {
+    fn from_structure(tag: structure::StructureTag) -> Result<Self, Error> {
+        expect_universal(&tag, universal::Types::Set, TagStructure::Constructed)?;
+        Self::from_implicit_structure(tag)
+    }
+
+    /// The members are left undecoded, as their types aren't known.
+    fn from_implicit_structure(tag: structure::StructureTag) -> Result<Self, Error> {
+        expect_structure(&tag, TagStructure::Constructed)?;
+        let (id, class) = (tag.id, tag.class);
+        let inner = tag.expect_constructed()?.into_iter().map(Tag::StructureTag).collect();
+
+        Ok(Set { id, class, inner })
+    }
+}
+
+impl default::Default for Set {
+    fn default() -> Self {
+        Set {
+            id: universal::Types::Set as u64,
+            class: TagClass::Universal,
+            inner: Vec::new(),
+        }
+    }
+}
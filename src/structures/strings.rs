@@ -0,0 +1,295 @@
+use std::default;
+use std::fmt;
+use universal;
+use structure;
+
+use super::{ASNTag, FromStructure, expect_universal, cer_string, string_content};
+use common::{TagClass, TagStructure};
+use error::Error;
+
+/// How the characters of a string type are encoded, and which of them it permits.
+#[derive(Clone, Copy)]
+enum Charset {
+    /// A subset of ASCII, one octet per character.
+    Ascii(fn(char) -> bool),
+    Utf8,
+    /// ISO 8859-1, one octet per character.
+    Latin1,
+    /// UCS-2, two big-endian octets per character.
+    Ucs2,
+    /// UCS-4, four big-endian octets per character.
+    Ucs4,
+}
+
+impl Charset {
+    fn allows(self, c: char) -> bool {
+        match self {
+            Charset::Ascii(allowed) => c.is_ascii() && allowed(c),
+            Charset::Utf8 | Charset::Ucs4 => true,
+            Charset::Latin1 => (c as u32) < 0x100,
+            Charset::Ucs2 => (c as u32) < 0x10000,
+        }
+    }
+
+    /// Check that every character of `s` is permitted, failing with the byte offset into `s` of
+    /// the first one that isn't.
+    fn check(self, s: &str) -> Result<(), Error> {
+        match s.char_indices().find(|&(_, c)| !self.allows(c)) {
+            Some((offset, _)) => Err(Error::InvalidContent { offset }),
+            None => Ok(()),
+        }
+    }
+
+    /// Encode `s`, which has to have passed `check`.
+    fn encode(self, s: &str) -> Vec<u8> {
+        match self {
+            Charset::Ascii(_) | Charset::Latin1 => s.chars().map(|x| x as u8).collect(),
+            Charset::Utf8 => s.as_bytes().to_vec(),
+            Charset::Ucs2 => s.chars().flat_map(|x| (x as u16).to_be_bytes()).collect(),
+            Charset::Ucs4 => s.chars().flat_map(|x| (x as u32).to_be_bytes()).collect(),
+        }
+    }
+
+    /// Number of octets `encode` turns `s` into.
+    fn encoded_len(self, s: &str) -> usize {
+        match self {
+            Charset::Ascii(_) | Charset::Latin1 => s.chars().count(),
+            Charset::Utf8 => s.len(),
+            Charset::Ucs2 => 2 * s.chars().count(),
+            Charset::Ucs4 => 4 * s.chars().count(),
+        }
+    }
+
+    fn decode(self, v: Vec<u8>) -> Result<String, Error> {
+        match self {
+            Charset::Ascii(_) | Charset::Latin1 => {
+                let s: String = v.iter().map(|&b| b as char).collect();
+                self.check(&s)?;
+                Ok(s)
+            },
+            Charset::Utf8 => String::from_utf8(v)
+                .map_err(|e| Error::InvalidContent { offset: e.utf8_error().valid_up_to() }),
+            Charset::Ucs2 => decode_units(&v, 2),
+            Charset::Ucs4 => decode_units(&v, 4),
+        }
+    }
+}
+
+/// Decode big-endian code units of `size` octets each.
+fn decode_units(v: &[u8], size: usize) -> Result<String, Error> {
+    if v.len() % size != 0 {
+        return Err(Error::InvalidContent { offset: v.len() - v.len() % size });
+    }
+
+    v.chunks(size).enumerate().map(|(n, unit)| {
+        let code = unit.iter().fold(0u32, |acc, &b| (acc << 8) | b as u32);
+        // Surrogates are not characters in UCS-2 or UCS-4.
+        ::std::char::from_u32(code).ok_or(Error::InvalidContent { offset: n * size })
+    }).collect()
+}
+
+fn is_numeric(c: char) -> bool {
+    c.is_ascii_digit() || c == ' '
+}
+
+fn is_printable(c: char) -> bool {
+    c.is_ascii_alphanumeric() || " '()+,-./:=?".contains(c)
+}
+
+fn is_ia5(_: char) -> bool {
+    true
+}
+
+fn is_visible(c: char) -> bool {
+    (' '..='~').contains(&c)
+}
+
+macro_rules! string_type {
+    ($(#[$attr:meta])* $name:ident, $ty:ident, $charset:expr) => {
+        $(#[$attr])*
+        ///
+        /// The string can only be set through `new`, which rejects characters the type does not
+        /// permit, so every value can be encoded.
+        #[derive(Clone, Debug, PartialEq, Eq)]
+        pub struct $name {
+            pub id: u64,
+            pub class: TagClass,
+            inner: String,
+        }
+
+        impl $name {
+            /// Create a universal string, checking that every character is permitted.
+            pub fn new<S: Into<String>>(s: S) -> Result<$name, Error> {
+                let inner = s.into();
+                $charset.check(&inner)?;
+                Ok($name { inner, .. Default::default() })
+            }
+
+            pub fn as_str(&self) -> &str {
+                &self.inner
+            }
+
+            pub fn into_string(self) -> String {
+                self.inner
+            }
+        }
+
+        impl ASNTag for $name {
+            fn into_structure(self) -> structure::StructureTag {
+                structure::StructureTag {
+                    id: self.id,
+                    class: self.class,
+                    payload: structure::PL::P($charset.encode(&self.inner)),
+                }
+            }
+
+            fn class_and_id(&self) -> (TagClass, u64) {
+                (self.class, self.id)
+            }
+
+            fn structure(&self) -> TagStructure {
+                TagStructure::Primitive
+            }
+
+            fn content_len(&self) -> usize {
+                $charset.encoded_len(&self.inner)
+            }
+
+            fn into_cer_structure(self) -> structure::StructureTag {
+                cer_string(self.class, self.id, $charset.encode(&self.inner), false)
+            }
+        }
+
+        impl FromStructure for $name {
+            fn from_structure(tag: structure::StructureTag) -> Result<Self, Error> {
+                expect_universal(&tag, universal::Types::$ty, tag.structure())?;
+                Self::from_implicit_structure(tag)
+            }
+
+            /// Segmented (constructed) strings are joined back into one.
+            fn from_implicit_structure(tag: structure::StructureTag) -> Result<Self, Error> {
+                let (id, class) = (tag.id, tag.class);
+                let inner = $charset.decode(string_content(tag, universal::Types::OctetString)?)?;
+
+                Ok($name { id, class, inner })
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str(&self.inner)
+            }
+        }
+
+        impl default::Default for $name {
+            fn default() -> Self {
+                $name {
+                    id: universal::Types::$ty as u64,
+                    class: TagClass::Universal,
+                    inner: String::new(),
+                }
+            }
+        }
+    }
+}
+
+string_type!(
+    /// A UTF8String, which permits any character.
+    Utf8String, Utf8String, Charset::Utf8);
+string_type!(
+    /// A NumericString, made up of digits and spaces.
+    NumericString, NumericString, Charset::Ascii(is_numeric));
+string_type!(
+    /// A PrintableString, made up of letters, digits, spaces and `'()+,-./:=?`.
+    PrintableString, PrintableString, Charset::Ascii(is_printable));
+string_type!(
+    /// An IA5String, which permits all of ASCII.
+    Ia5String, Ia5String, Charset::Ascii(is_ia5));
+string_type!(
+    /// A VisibleString, which permits printing ASCII characters and the space.
+    VisibleString, VisibleString, Charset::Ascii(is_visible));
+string_type!(
+    /// A T61String (TeletexString). Real T.61 is a stateful, multi-byte encoding that is next to
+    /// never used; like most implementations, this treats the content as ISO 8859-1.
+    T61String, T61String, Charset::Latin1);
+string_type!(
+    /// A BMPString, holding characters of the Basic Multilingual Plane as UCS-2.
+    BmpString, BmpString, Charset::Ucs2);
+string_type!(
+    /// A UniversalString, holding any character as UCS-4.
+    UniversalString, UniversalString, Charset::Ucs4);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use structure::{StructureTag, PL};
+
+    fn string_tag(id: u64, content: Vec<u8>) -> StructureTag {
+        StructureTag { class: TagClass::Universal, id, payload: PL::P(content) }
+    }
+
+    #[test]
+    fn ascii_alphabets() {
+        let s = PrintableString::new("Example Org.").unwrap();
+        assert_eq!(s.clone().into_structure(), string_tag(19, b"Example Org.".to_vec()));
+        assert_eq!(PrintableString::from_structure(string_tag(19, b"Example Org.".to_vec())), Ok(s));
+
+        assert_eq!(PrintableString::new("a@b").err(), Some(Error::InvalidContent { offset: 1 }));
+        assert_eq!(PrintableString::from_structure(string_tag(19, b"a*b".to_vec())).err(),
+                   Some(Error::InvalidContent { offset: 1 }));
+        assert_eq!(NumericString::new("12 34").map(|s| s.into_string()), Ok("12 34".to_string()));
+        assert_eq!(NumericString::new("12a").err(), Some(Error::InvalidContent { offset: 2 }));
+        assert_eq!(VisibleString::new("tab\t").err(), Some(Error::InvalidContent { offset: 3 }));
+        assert_eq!(Ia5String::new("user@example.com").map(|s| s.to_string()), Ok("user@example.com".to_string()));
+        assert_eq!(Ia5String::new("caf\u{e9}").err(), Some(Error::InvalidContent { offset: 3 }));
+        assert_eq!(Ia5String::from_structure(string_tag(22, vec![0x61, 0xE9])).err(),
+                   Some(Error::InvalidContent { offset: 1 }));
+    }
+
+    #[test]
+    fn utf8_and_latin1() {
+        let s = Utf8String::new("gr\u{fc}n").unwrap();
+        assert_eq!(s.clone().into_structure(), string_tag(12, vec![0x67, 0x72, 0xC3, 0xBC, 0x6E]));
+        assert_eq!(Utf8String::from_structure(s.clone().into_structure()), Ok(s));
+        assert_eq!(Utf8String::from_structure(string_tag(12, vec![0x61, 0xC3])).err(),
+                   Some(Error::InvalidContent { offset: 1 }));
+
+        let s = T61String::new("gr\u{fc}n").unwrap();
+        assert_eq!(s.clone().into_structure(), string_tag(20, vec![0x67, 0x72, 0xFC, 0x6E]));
+        assert_eq!(T61String::from_structure(s.clone().into_structure()), Ok(s));
+        assert_eq!(T61String::new("\u{20ac}").err(), Some(Error::InvalidContent { offset: 0 }));
+    }
+
+    #[test]
+    fn wide_strings() {
+        let s = BmpString::new("a\u{20ac}").unwrap();
+        assert_eq!(s.clone().into_structure(), string_tag(30, vec![0x00, 0x61, 0x20, 0xAC]));
+        assert_eq!(BmpString::from_structure(s.clone().into_structure()), Ok(s));
+        assert_eq!(BmpString::new("a\u{1f600}").err(), Some(Error::InvalidContent { offset: 1 }));
+        assert_eq!(BmpString::from_structure(string_tag(30, vec![0x00, 0x61, 0xD8, 0x3D])).err(),
+                   Some(Error::InvalidContent { offset: 2 }));
+        assert_eq!(BmpString::from_structure(string_tag(30, vec![0x00, 0x61, 0x00])).err(),
+                   Some(Error::InvalidContent { offset: 2 }));
+
+        let s = UniversalString::new("\u{1f600}").unwrap();
+        assert_eq!(s.clone().into_structure(), string_tag(28, vec![0x00, 0x01, 0xF6, 0x00]));
+        assert_eq!(UniversalString::from_structure(s.clone().into_structure()), Ok(s));
+        assert_eq!(UniversalString::from_structure(string_tag(28, vec![0x00, 0x11, 0x00, 0x00])).err(),
+                   Some(Error::InvalidContent { offset: 0 }));
+    }
+
+    #[test]
+    fn segmented_string() {
+        let tag = StructureTag { class: TagClass::Universal, id: 12, payload: PL::C(vec![
+            string_tag(4, vec![0x67, 0x72, 0xC3]),
+            string_tag(4, vec![0xBC, 0x6E]),
+        ])};
+        assert_eq!(Utf8String::from_structure(tag).map(|s| s.inner), Ok("gr\u{fc}n".to_string()));
+
+        let s = Utf8String { id: 1, class: TagClass::Context, inner: "a".repeat(1001) };
+        match s.into_cer_structure().into_payload() {
+            PL::C(segments) => assert_eq!(segments.len(), 2),
+            PL::P(_) => panic!("expected a segmented string"),
+        }
+    }
+}
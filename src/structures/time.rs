@@ -0,0 +1,454 @@
+use std::default;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use universal;
+use structure;
+
+use super::{ASNTag, FromStructure, expect_universal, expect_structure};
+use common::{TagClass, TagStructure};
+use error::Error;
+
+/// A UTCTime, which can only hold whole seconds in the years 1950 to 2049.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UtcTime {
+    pub id: u64,
+    pub class: TagClass,
+    pub inner: SystemTime,
+}
+
+/// A GeneralizedTime, which can hold the years 0 to 9999 with sub-second precision.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GeneralizedTime {
+    pub id: u64,
+    pub class: TagClass,
+    pub inner: SystemTime,
+}
+
+impl UtcTime {
+    /// Create a universal UTCTime, dropping the fraction of a second. Fails if the year is not
+    /// between 1950 and 2049.
+    pub fn new(time: SystemTime) -> Result<UtcTime, Error> {
+        let (secs, _) = to_unix(time);
+        let (year, _, _) = civil_from_days(secs.div_euclid(86400));
+        if !(1950..2050).contains(&year) {
+            return Err(Error::Overflow { offset: 0 });
+        }
+
+        let inner = from_unix(secs, 0).ok_or(Error::Overflow { offset: 0 })?;
+        Ok(UtcTime { inner, .. Default::default() })
+    }
+}
+
+impl GeneralizedTime {
+    /// Create a universal GeneralizedTime. Fails if the year is not between 0 and 9999.
+    pub fn new(time: SystemTime) -> Result<GeneralizedTime, Error> {
+        let (secs, _) = to_unix(time);
+        let (year, _, _) = civil_from_days(secs.div_euclid(86400));
+        if !(0..10000).contains(&year) {
+            return Err(Error::Overflow { offset: 0 });
+        }
+
+        Ok(GeneralizedTime { inner: time, .. Default::default() })
+    }
+}
+
+/// Seconds and nanoseconds since the Unix epoch, with the seconds rounded down.
+fn to_unix(time: SystemTime) -> (i64, u32) {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(d) => (d.as_secs() as i64, d.subsec_nanos()),
+        Err(e) => {
+            let d = e.duration();
+            if d.subsec_nanos() == 0 {
+                (-(d.as_secs() as i64), 0)
+            } else {
+                (-(d.as_secs() as i64) - 1, 1_000_000_000 - d.subsec_nanos())
+            }
+        }
+    }
+}
+
+/// The time `secs` seconds and `nanos` nanoseconds after the Unix epoch, or `None` if the platform's
+/// `SystemTime` can't represent it, as on Windows for years before 1601.
+fn from_unix(secs: i64, nanos: u32) -> Option<SystemTime> {
+    if secs >= 0 {
+        UNIX_EPOCH.checked_add(Duration::new(secs as u64, nanos))
+    } else {
+        UNIX_EPOCH.checked_sub(Duration::new(secs.unsigned_abs(), 0))?.checked_add(Duration::new(0, nanos))
+    }
+}
+
+/// Days since 1970-01-01 of a date in the proleptic Gregorian calendar.
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let m = month as i64;
+    let doy = (153 * (if m > 2 { m - 3 } else { m + 9 }) + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+/// The date `days` days after 1970-01-01.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    (if month <= 2 { yoe + era * 400 + 1 } else { yoe + era * 400 }, month, day)
+}
+
+fn days_in_month(year: i64, month: u32) -> u32 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Cursor over the characters of a time value.
+struct Reader<'a> {
+    v: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn peek(&self) -> Option<u8> {
+        self.v.get(self.pos).cloned()
+    }
+
+    fn next_is_digit(&self) -> bool {
+        matches!(self.peek(), Some(c) if c.is_ascii_digit())
+    }
+
+    /// Read exactly `n` digits as a number between `min` and `max`.
+    fn number(&mut self, n: usize, min: u32, max: u32) -> Result<u32, Error> {
+        let start = self.pos;
+        let mut value = 0;
+        for _ in 0..n {
+            match self.peek() {
+                Some(c) if c.is_ascii_digit() => value = value * 10 + (c - b'0') as u32,
+                _ => return Err(Error::InvalidContent { offset: self.pos }),
+            }
+            self.pos += 1;
+        }
+        if value < min || value > max {
+            return Err(Error::InvalidContent { offset: start });
+        }
+        Ok(value)
+    }
+}
+
+/// Parse the content of a UTCTime (`utc`) or GeneralizedTime into seconds and nanoseconds since
+/// the Unix epoch.
+///
+/// In `strict` mode only the form DER and CER allow is accepted: seconds always present, a
+/// fraction only in GeneralizedTime, written with '.' and without trailing zeros, and a
+/// closing 'Z'.
+fn parse_time(v: &[u8], utc: bool, strict: bool) -> Result<(i64, u32), Error> {
+    let mut r = Reader { v, pos: 0 };
+
+    let year = if utc {
+        // Two-digit years from 50 on are in the 20th century.
+        match r.number(2, 0, 99)? as i64 {
+            y if y < 50 => 2000 + y,
+            y => 1900 + y,
+        }
+    } else {
+        r.number(4, 0, 9999)? as i64
+    };
+    let month = r.number(2, 1, 12)?;
+    let day = r.number(2, 1, days_in_month(year, month))?;
+    let hour = r.number(2, 0, 23)? as i64;
+
+    // The fraction applies to the last of hour, minute and second present.
+    let mut unit = 3600;
+    let minute = if utc || r.next_is_digit() {
+        unit = 60;
+        r.number(2, 0, 59)? as i64
+    } else {
+        0
+    };
+    let second = if unit == 60 && r.next_is_digit() {
+        unit = 1;
+        // 60 for a leap second, which ends up as the first second of the next minute.
+        r.number(2, 0, 60)? as i64
+    } else {
+        if strict {
+            return Err(Error::NonCanonical { offset: r.pos });
+        }
+        0
+    };
+
+    let mut nanos: u128 = 0;
+    if !utc && (r.peek() == Some(b'.') || r.peek() == Some(b',')) {
+        if strict && r.peek() == Some(b',') {
+            return Err(Error::NonCanonical { offset: r.pos });
+        }
+        r.pos += 1;
+
+        let start = r.pos;
+        let (mut value, mut scale): (u128, u128) = (0, 1);
+        while let Some(c) = r.peek().filter(|c| c.is_ascii_digit()) {
+            // Digits beyond what a nanosecond of an hour needs can't change the value.
+            if r.pos - start < 18 {
+                value = value * 10 + (c - b'0') as u128;
+                scale *= 10;
+            }
+            r.pos += 1;
+        }
+        if r.pos == start {
+            return Err(Error::InvalidContent { offset: start });
+        }
+        if strict && v[r.pos - 1] == b'0' {
+            return Err(Error::NonCanonical { offset: r.pos - 1 });
+        }
+        nanos = value * unit * 1_000_000_000 / scale;
+    }
+
+    let offset = match r.peek() {
+        Some(b'Z') => {
+            r.pos += 1;
+            0
+        },
+        Some(sign @ b'+') | Some(sign @ b'-') => {
+            if strict {
+                return Err(Error::NonCanonical { offset: r.pos });
+            }
+            r.pos += 1;
+            let hours = r.number(2, 0, 23)? as i64;
+            let minutes = if utc || r.next_is_digit() { r.number(2, 0, 59)? as i64 } else { 0 };
+            let offset = hours * 3600 + minutes * 60;
+            if sign == b'+' { offset } else { -offset }
+        },
+        // Local time in a GeneralizedTime. The time zone of the sender is unknown, so it is
+        // taken to be UTC.
+        None if !utc && !strict => 0,
+        _ => return Err(Error::InvalidContent { offset: r.pos }),
+    };
+    if r.pos != v.len() {
+        return Err(Error::InvalidContent { offset: r.pos });
+    }
+
+    let secs = days_from_civil(year, month, day) * 86400 + hour * 3600 + minute * 60 + second
+             - offset + (nanos / 1_000_000_000) as i64;
+    Ok((secs, (nanos % 1_000_000_000) as u32))
+}
+
+/// Check that the content of a UTCTime or GeneralizedTime is in the form DER and CER require.
+pub(crate) fn check_canonical(id: u64, v: &[u8]) -> Result<(), Error> {
+    parse_time(v, id == universal::Types::UtcTime as u64, true).map(|_| ())
+}
+
+/// Format a time as `YYYYMMDDHHMMSS`, followed by the fraction of a second if `fraction` is set
+/// and there is one, and 'Z'.
+fn format_time(time: SystemTime, fraction: bool) -> String {
+    let (secs, nanos) = to_unix(time);
+    let (year, month, day) = civil_from_days(secs.div_euclid(86400));
+    let rem = secs.rem_euclid(86400);
+
+    let mut out = format!("{:04}{:02}{:02}{:02}{:02}{:02}",
+                          year, month, day, rem / 3600, rem / 60 % 60, rem % 60);
+    if fraction && nanos > 0 {
+        out.push_str(format!(".{:09}", nanos).trim_end_matches('0'));
+    }
+    out.push('Z');
+    out
+}
+
+impl ASNTag for UtcTime {
+    fn into_structure(self) -> structure::StructureTag {
+        let time = format_time(self.inner, false);
+
+        structure::StructureTag {
+            id: self.id,
+            class: self.class,
+            payload: structure::PL::P(time.as_bytes()[2..].to_vec()),
+        }
+    }
+
+    fn class_and_id(&self) -> (TagClass, u64) {
+        (self.class, self.id)
+    }
+
+    fn structure(&self) -> TagStructure {
+        TagStructure::Primitive
+    }
+
+    fn content_len(&self) -> usize {
+        format_time(self.inner, false).len() - 2
+    }
+}
+
+impl ASNTag for GeneralizedTime {
+    fn into_structure(self) -> structure::StructureTag {
+        structure::StructureTag {
+            id: self.id,
+            class: self.class,
+            payload: structure::PL::P(format_time(self.inner, true).into_bytes()),
+        }
+    }
+
+    fn class_and_id(&self) -> (TagClass, u64) {
+        (self.class, self.id)
+    }
+
+    fn structure(&self) -> TagStructure {
+        TagStructure::Primitive
+    }
+
+    fn content_len(&self) -> usize {
+        format_time(self.inner, true).len()
+    }
+}
+
+impl FromStructure for UtcTime {
+    fn from_structure(tag: structure::StructureTag) -> Result<Self, Error> {
+        expect_universal(&tag, universal::Types::UtcTime, TagStructure::Primitive)?;
+        Self::from_implicit_structure(tag)
+    }
+
+    fn from_implicit_structure(tag: structure::StructureTag) -> Result<Self, Error> {
+        expect_structure(&tag, TagStructure::Primitive)?;
+        let (id, class) = (tag.id, tag.class);
+        let (secs, nanos) = parse_time(&tag.expect_primitive()?, true, false)?;
+
+        let inner = from_unix(secs, nanos).ok_or(Error::Overflow { offset: 0 })?;
+        Ok(UtcTime { id, class, inner })
+    }
+}
+
+impl FromStructure for GeneralizedTime {
+    fn from_structure(tag: structure::StructureTag) -> Result<Self, Error> {
+        expect_universal(&tag, universal::Types::GeneralizedTime, TagStructure::Primitive)?;
+        Self::from_implicit_structure(tag)
+    }
+
+    fn from_implicit_structure(tag: structure::StructureTag) -> Result<Self, Error> {
+        expect_structure(&tag, TagStructure::Primitive)?;
+        let (id, class) = (tag.id, tag.class);
+        let (secs, nanos) = parse_time(&tag.expect_primitive()?, false, false)?;
+
+        let inner = from_unix(secs, nanos).ok_or(Error::Overflow { offset: 0 })?;
+        Ok(GeneralizedTime { id, class, inner })
+    }
+}
+
+impl From<UtcTime> for SystemTime {
+    fn from(time: UtcTime) -> SystemTime {
+        time.inner
+    }
+}
+
+impl From<GeneralizedTime> for SystemTime {
+    fn from(time: GeneralizedTime) -> SystemTime {
+        time.inner
+    }
+}
+
+impl default::Default for UtcTime {
+    fn default() -> Self {
+        UtcTime {
+            id: universal::Types::UtcTime as u64,
+            class: TagClass::Universal,
+            inner: UNIX_EPOCH,
+        }
+    }
+}
+
+impl default::Default for GeneralizedTime {
+    fn default() -> Self {
+        GeneralizedTime {
+            id: universal::Types::GeneralizedTime as u64,
+            class: TagClass::Universal,
+            inner: UNIX_EPOCH,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use structure::{StructureTag, PL};
+
+    fn time_tag(id: u64, content: &str) -> StructureTag {
+        StructureTag { class: TagClass::Universal, id, payload: PL::P(content.as_bytes().to_vec()) }
+    }
+
+    fn utc(content: &str) -> Result<SystemTime, Error> {
+        UtcTime::from_structure(time_tag(23, content)).map(SystemTime::from)
+    }
+
+    fn generalized(content: &str) -> Result<SystemTime, Error> {
+        GeneralizedTime::from_structure(time_tag(24, content)).map(SystemTime::from)
+    }
+
+    #[test]
+    fn utc_time() {
+        let time = from_unix(1_000_000_000, 0).unwrap();
+        let tag = UtcTime::new(time).unwrap().into_structure();
+        assert_eq!(tag, time_tag(23, "010909014640Z"));
+        assert_eq!(utc("010909014640Z"), Ok(time));
+
+        // Missing seconds and local offsets
+        assert_eq!(utc("0109090146Z"), Ok(from_unix(1_000_000_000 - 40, 0).unwrap()));
+        assert_eq!(utc("010909034640+0200"), Ok(time));
+        assert_eq!(utc("0109082316-0230"), Ok(from_unix(1_000_000_000 - 40, 0).unwrap()));
+
+        // The century pivot
+        assert_eq!(utc("500101000000Z"), Ok(from_unix(-631152000, 0).unwrap()));
+        assert_eq!(utc("491231235959Z"), Ok(from_unix(2524607999, 0).unwrap()));
+        assert_eq!(UtcTime::new(from_unix(2524608000, 0).unwrap()), Err(Error::Overflow { offset: 0 }));
+
+        // Sub-seconds are dropped, times before the epoch work.
+        assert_eq!(UtcTime::new(from_unix(-1, 500).unwrap()).unwrap().into_structure(), time_tag(23, "691231235959Z"));
+    }
+
+    #[test]
+    fn generalized_time() {
+        let time = from_unix(1_000_000_000, 250_000_000).unwrap();
+        let tag = GeneralizedTime::new(time).unwrap().into_structure();
+        assert_eq!(tag, time_tag(24, "20010909014640.25Z"));
+        assert_eq!(generalized("20010909014640.25Z"), Ok(time));
+        assert_eq!(generalized("20010909014640,250Z"), Ok(time));
+        assert_eq!(generalized("20010909034640.25+02"), Ok(time));
+
+        // Fractions of minutes and hours, and local time
+        assert_eq!(generalized("200109090146.5Z"), Ok(from_unix(1_000_000_000 - 10, 0).unwrap()));
+        assert_eq!(generalized("2001090901.5Z"), Ok(from_unix(1_000_000_000 - 16 * 60 - 40, 0).unwrap()));
+        assert_eq!(generalized("20010909014640"), Ok(from_unix(1_000_000_000, 0).unwrap()));
+
+        assert_eq!(GeneralizedTime::new(from_unix(0, 0).unwrap()).unwrap().into_structure(), time_tag(24, "19700101000000Z"));
+        assert_eq!(generalized("00000101000000Z"), Ok(from_unix(-62167219200, 0).unwrap()));
+    }
+
+    #[test]
+    fn invalid_time() {
+        assert_eq!(utc("011309014640Z"), Err(Error::InvalidContent { offset: 2 }));
+        assert_eq!(utc("010229014640Z"), Err(Error::InvalidContent { offset: 4 }));
+        assert_eq!(utc("0109090146"), Err(Error::InvalidContent { offset: 10 }));
+        assert_eq!(utc("010909014640.5Z"), Err(Error::InvalidContent { offset: 12 }));
+        assert_eq!(utc("010909014640+02"), Err(Error::InvalidContent { offset: 15 }));
+        assert_eq!(generalized("20010909014640.Z"), Err(Error::InvalidContent { offset: 15 }));
+        assert_eq!(generalized("20000229246000Z"), Err(Error::InvalidContent { offset: 8 }));
+        assert_eq!(generalized("20010909014640Zx"), Err(Error::InvalidContent { offset: 15 }));
+    }
+
+    #[test]
+    fn canonical_time() {
+        let utc = universal::Types::UtcTime as u64;
+        let generalized = universal::Types::GeneralizedTime as u64;
+
+        assert_eq!(check_canonical(utc, b"010909014640Z"), Ok(()));
+        assert_eq!(check_canonical(utc, b"0109090146Z"), Err(Error::NonCanonical { offset: 10 }));
+        assert_eq!(check_canonical(utc, b"010909034640+0200"), Err(Error::NonCanonical { offset: 12 }));
+
+        assert_eq!(check_canonical(generalized, b"20010909014640.25Z"), Ok(()));
+        assert_eq!(check_canonical(generalized, b"20010909014640,25Z"), Err(Error::NonCanonical { offset: 14 }));
+        assert_eq!(check_canonical(generalized, b"20010909014640.250Z"), Err(Error::NonCanonical { offset: 17 }));
+        assert_eq!(check_canonical(generalized, b"20010909014640"), Err(Error::InvalidContent { offset: 14 }));
+    }
+}
@@ -1,36 +1,161 @@
+use common::TagStructure;
+use error::Error;
+use structure::{StructureTag, PL};
+use structures::{ASNTag, FromStructure};
+use parse::{parse_tag, Rules};
+use write;
+
+use nom::{IResult, ErrorKind};
+
+/// A value that encodes to a complete element, identifier and length octets included, under the
+/// tag of its own type.
 pub trait AsBER : Sized {
-    fn encode_into(&self, &mut Vec<u8>);
+    fn encode_into(&self, buf: &mut Vec<u8>);
     fn encode(&self) -> Vec<u8> {
         let mut out: Vec<u8> = Vec::new();
         self.encode_into(&mut out);
-        return out;
+        out
     }
 
-    fn decode(&[u8]) -> Option<Self>;
+    fn decode(i: &[u8]) -> IResult<&[u8], Self, Error>;
 
+    /// Number of octets of the encoding.
     fn len(&self) -> u64;
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
 }
 
+/// The content octets of a value, without identifier and length, so that it can be put under
+/// any tag.
 pub trait BERPayload : Sized {
-    fn encode_into(&self, &mut Vec<u8>);
+    /// Whether the content is primitive or made up of other elements.
+    fn structure(&self) -> TagStructure;
+
+    fn encode_into(&self, buf: &mut Vec<u8>);
     fn encode(&self) -> Vec<u8> {
         let mut out: Vec<u8> = Vec::new();
         self.encode_into(&mut out);
-        return out;
+        out
     }
 
-    fn decode(&[u8]) -> Option<Self>;
+    /// Decode the content octets of an element with the given structure.
+    fn decode(structure: TagStructure, content: &[u8]) -> Result<Self, Error>;
 
+    /// Number of content octets.
     fn len(&self) -> u64;
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
 }
 
+/// A complete element with a tag chosen at runtime.
 pub trait BERTag : Sized {
-    fn encode_into(&self, &mut Vec<u8>);
+    fn encode_into(&self, buf: &mut Vec<u8>);
     fn encode(&self) -> Vec<u8> {
         let mut out: Vec<u8> = Vec::new();
         self.encode_into(&mut out);
-        return out;
+        out
+    }
+
+    fn decode(i: &[u8]) -> IResult<&[u8], Self, Error>;
+}
+
+impl<T: ASNTag + FromStructure + Clone> AsBER for T {
+    fn encode_into(&self, buf: &mut Vec<u8>) {
+        buf.extend(self.encode());
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        write::encode_to_vec(&self.clone().into_structure(), Rules::Ber)
+    }
+
+    fn decode(i: &[u8]) -> IResult<&[u8], Self, Error> {
+        let (rest, tag) = try_parse!(i, parse_tag);
+        match T::from_structure(tag) {
+            Ok(value) => IResult::Done(rest, value),
+            Err(e) => IResult::Error(ErrorKind::Custom(e)),
+        }
+    }
+
+    fn len(&self) -> u64 {
+        ASNTag::encoded_len(self) as u64
+    }
+}
+
+/// Every type from `structures` is a payload. Values decoded this way carry the class and id of
+/// their own type, as `Default` has them.
+impl<T: ASNTag + FromStructure + Clone + Default> BERPayload for T {
+    fn structure(&self) -> TagStructure {
+        ASNTag::structure(self)
+    }
+
+    fn encode_into(&self, buf: &mut Vec<u8>) {
+        match self.clone().into_structure().into_payload() {
+            PL::P(v) => buf.extend(v),
+            PL::C(tags) => for tag in tags {
+                buf.extend(write::encode_to_vec(&tag, Rules::Ber));
+            },
+        }
+    }
+
+    fn decode(structure: TagStructure, content: &[u8]) -> Result<Self, Error> {
+        let payload = match structure {
+            TagStructure::Primitive => PL::P(content.to_vec()),
+            TagStructure::Constructed => PL::C(parse_children(content)?),
+        };
+
+        let (class, id) = T::default().class_and_id();
+        T::from_implicit_structure(StructureTag { class, id, payload })
+    }
+
+    fn len(&self) -> u64 {
+        self.content_len() as u64
+    }
+}
+
+/// Parse content octets that consist of complete elements.
+fn parse_children(content: &[u8]) -> Result<Vec<StructureTag>, Error> {
+    let mut tags = Vec::new();
+    let mut rest = content;
+    while !rest.is_empty() {
+        let pos = content.len() - rest.len();
+        match parse_tag(rest) {
+            IResult::Done(r, tag) => {
+                tags.push(tag);
+                rest = r;
+            },
+            IResult::Error(e) => return Err(Error::from(e).shift(pos)),
+            IResult::Incomplete(_) => return Err(Error::Truncated { offset: pos }),
+        }
     }
+    Ok(tags)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use structures::{Integer, OctetString, Sequence, Tag};
+
+    use std::default::Default;
 
-    fn decode(&[u8]) -> Option<Self>;
+    #[test]
+    fn as_ber() {
+        let int = Integer { inner: 300, .. Default::default() };
+        assert_eq!(AsBER::encode(&int), vec![0x02, 0x02, 0x01, 0x2C]);
+        assert_eq!(AsBER::len(&int), 4);
+        assert_eq!(<Integer as AsBER>::decode(&[0x02, 0x02, 0x01, 0x2C, 0xFF][..]), IResult::Done(&[0xFF][..], int));
+    }
+
+    #[test]
+    fn ber_payload() {
+        let string = OctetString { inner: vec![0x61, 0x62], .. Default::default() };
+        assert_eq!(BERPayload::encode(&string), vec![0x61, 0x62]);
+        assert_eq!(<OctetString as BERPayload>::decode(TagStructure::Primitive, &[0x61, 0x62]), Ok(string));
+
+        let seq = Sequence { inner: vec![Tag::Integer(Integer { inner: 1, .. Default::default() })], .. Default::default() };
+        assert_eq!(BERPayload::encode(&seq), vec![0x02, 0x01, 0x01]);
+        assert_eq!(<Sequence as BERPayload>::decode(TagStructure::Constructed, &[0x02, 0x01]),
+                   Err(Error::Truncated { offset: 0 }));
+    }
 }
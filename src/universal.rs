@@ -29,3 +29,9 @@ pub enum Types {
     CharacterString  = 29,
     BmpString        = 30,
 }
+
+/// Whether a universal tag number belongs to a string type, whose value BER allows to be split
+/// into a constructed sequence of segments.
+pub fn is_string_type(id: u64) -> bool {
+    matches!(id, 3 | 4 | 7 | 12 | 18..=28 | 30)
+}
@@ -1,42 +1,598 @@
 use common::{TagClass, TagStructure};
 use structure::{StructureTag, PL};
+use error::Error;
+use universal;
+use parse::Rules;
 
-use std::io;
+use std::borrow::Cow;
+use std::cmp::Ordering;
 use std::io::Write;
+use std::slice;
 
 use byteorder::BigEndian;
 use byteorder::WriteBytesExt;
 
-pub fn encode_into(buf: &mut Vec<u8>, tag: StructureTag) -> io::Result<()> {
-    let structure = match tag.payload {
-        PL::P(_) => TagStructure::Primitive,
-        PL::C(_) => TagStructure::Constructed,
+/// Encode a tag using the definite length form for every constructed tag.
+///
+/// `buf` is grown to the size of the encoding up front; see `encode_definite`.
+pub fn encode_into(buf: &mut Vec<u8>, tag: StructureTag) -> Result<(), Error> {
+    let lengths = content_lengths(&tag);
+    buf.reserve(total_len(&tag, &lengths));
+    write_definite(buf, &tag, lengths)
+}
+
+/// Encode a tag using the definite length form for every constructed tag, writing to `w`.
+///
+/// The lengths of all constructed tags are worked out first, so that every octet is written only
+/// once and nothing is buffered. Nested tags are kept on a stack instead of recursing, so that
+/// deep nesting can't overflow the call stack.
+pub fn encode_definite(w: &mut dyn Write, tag: StructureTag) -> Result<(), Error> {
+    let lengths = content_lengths(&tag);
+    write_definite(w, &tag, lengths)
+}
+
+/// Encode a tag under `rules` into a new buffer.
+pub(crate) fn encode_to_vec(tag: &StructureTag, rules: Rules) -> Vec<u8> {
+    let mut buf = Vec::new();
+    let res = match rules {
+        Rules::Ber => {
+            let lengths = content_lengths(tag);
+            buf.reserve(total_len(tag, &lengths));
+            write_definite(&mut buf, tag, lengths)
+        },
+        Rules::Der | Rules::Cer => write_canonical(&mut buf, tag, rules),
     };
+    // The encoders only fail when the writer does, and writing into a Vec doesn't.
+    res.expect("encoding into a Vec");
+    buf
+}
 
-    write_type(buf, tag.class, structure, tag.id);
-    match tag.payload {
-        PL::P(v) => {
-            write_length(buf, v.len());
-            for byte in v {
-                buf.push(byte);
+/// Write the identifier and length octets of a tag into `buf`.
+pub(crate) fn write_header(buf: &mut Vec<u8>, class: TagClass, structure: TagStructure, id: u64, len: usize) {
+    // Like the encoders, these only fail when the writer does.
+    write_type(buf, class, structure, id)
+        .and_then(|_| write_length(buf, len))
+        .expect("encoding into a Vec");
+}
+
+fn write_definite(w: &mut dyn Write, tag: &StructureTag, lengths: Vec<usize>) -> Result<(), Error> {
+    let mut lengths = lengths.into_iter();
+    // Children left to encode of the constructed tags being encoded.
+    let mut stack: Vec<slice::Iter<StructureTag>> = Vec::new();
+    let mut next = Some(tag);
+
+    loop {
+        if let Some(tag) = next.take() {
+            match tag.payload {
+                PL::P(ref v) => {
+                    write_type(w, tag.class, TagStructure::Primitive, tag.id)?;
+                    write_length(w, v.len())?;
+                    w.write_all(v)?;
+                },
+                PL::C(ref tags) => {
+                    write_type(w, tag.class, TagStructure::Constructed, tag.id)?;
+                    write_length(w, lengths.next().unwrap_or(0))?;
+                    stack.push(tags.iter());
+                },
             }
-        },
-        PL::C(tags) => {
-            let mut tmp: Vec<u8> = Vec::new();
-            for tag in tags {
-                try!(encode_into(&mut tmp, tag));
+        }
+
+        next = match stack.last_mut() {
+            Some(top) => top.next(),
+            None => return Ok(()),
+        };
+        if next.is_none() {
+            stack.pop();
+        }
+    }
+}
+
+/// The content lengths of the constructed tags in `tag`, in the order they are written.
+fn content_lengths(tag: &StructureTag) -> Vec<usize> {
+    form_lengths(tag, Form::of)
+}
+
+/// Like `content_lengths`, with every tag in the form `form` gives it.
+fn form_lengths<'a>(tag: &'a StructureTag, form: fn(&'a StructureTag) -> Form<'a>) -> Vec<usize> {
+    let mut lengths = Vec::new();
+    // Constructed tags being measured, each with the index of its length, its id, the children
+    // left to measure and the length of those measured.
+    let mut stack: Vec<(usize, u64, slice::Iter<'a, StructureTag>, usize)> = Vec::new();
+    let mut next = Some(tag);
+
+    loop {
+        if let Some(tag) = next.take() {
+            match form(tag) {
+                Form::Primitive(v) => if let Some(top) = stack.last_mut() {
+                    top.3 += type_len(tag.id) + length_len(v.len()) + v.len();
+                },
+                Form::Constructed(tags) => {
+                    stack.push((lengths.len(), tag.id, tags.iter(), 0));
+                    lengths.push(0);
+                },
             }
-            write_length(buf, tmp.len());
-            for byte in tmp {
-                buf.push(byte);
+        }
+
+        next = match stack.last_mut() {
+            Some(top) => top.2.next(),
+            None => return lengths,
+        };
+        if next.is_none() {
+            let (n, id, _, len) = stack.pop().unwrap();
+            lengths[n] = len;
+            if let Some(top) = stack.last_mut() {
+                top.3 += type_len(id) + length_len(len) + len;
             }
         }
+    }
+}
+
+/// Encode a tag using the Distinguished Encoding Rules.
+///
+/// Universal tags are brought into their canonical form on the way: booleans are written as 0xFF,
+/// integers and enumerations without redundant leading octets, string types as a single primitive
+/// and the members of a SET sorted by tag, and those with the same tag by their encoding, unless
+/// they are sorted by encoding alone as a SET OF is already. Tags of other classes are written as
+/// they are, so implicitly tagged values have to be canonical already;
+/// `ASNTag::into_der_structure` takes care of that for the types in `structures`.
+pub fn encode_der_into(buf: &mut Vec<u8>, tag: StructureTag) -> Result<(), Error> {
+    write_canonical(buf, &tag, Rules::Der)
+}
+
+/// Encode a tag using the Canonical Encoding Rules.
+///
+/// Constructed tags are written with indefinite length like with `encode_indefinite`, and
+/// universal tags are canonicalized like with `encode_der_into`, except that string values longer
+/// than 1000 octets are split into a constructed string of 1000 octet segments.
+/// `ASNTag::into_cer_structure` does the same for implicitly tagged values from `structures`.
+pub fn encode_cer(w: &mut dyn Write, tag: StructureTag) -> Result<(), Error> {
+    write_canonical(w, &tag, Rules::Cer)
+}
+
+/// A constructed tag `write_canonical` is in the middle of.
+struct CanonicalFrame<'a> {
+    class: TagClass,
+    id: u64,
+    children: slice::Iter<'a, StructureTag>,
+    /// The encodings of the members done, if it is a universal SET.
+    members: Option<Vec<SetMember>>,
+    /// Whether it is itself a member of a SET, with a buffer of its own.
+    member: bool,
+}
+
+/// Encode a tag under DER or CER, bringing universal tags into their canonical form.
+///
+/// The members of a universal SET are encoded into buffers of their own, and written once they
+/// are sorted; everything else goes straight to `w`. Nested tags are kept on a stack instead of
+/// recursing, so that deep nesting can't overflow the call stack.
+fn write_canonical(w: &mut dyn Write, tag: &StructureTag, rules: Rules) -> Result<(), Error> {
+    // DER wants the length of constructed content before the content.
+    let mut lengths = match rules {
+        Rules::Der => form_lengths(tag, Form::der).into_iter(),
+        _ => Vec::new().into_iter(),
     };
+    let mut stack: Vec<CanonicalFrame> = Vec::new();
+    // Buffers of the SET members being encoded, innermost last.
+    let mut buffers: Vec<Vec<u8>> = Vec::new();
+    let mut next = Some(tag);
+
+    loop {
+        if let Some(tag) = next.take() {
+            let member = matches!(stack.last(), Some(top) if top.members.is_some());
+            if member {
+                buffers.push(Vec::new());
+            }
+            let out: &mut dyn Write = match buffers.last_mut() {
+                Some(buf) => buf,
+                None => &mut *w,
+            };
+
+            match Form::der(tag) {
+                Form::Primitive(v) => {
+                    write_canonical_primitive(out, tag.class, tag.id, &v, rules)?;
+                    if member {
+                        add_member(&mut stack, &mut buffers, tag.class, tag.id);
+                    }
+                },
+                Form::Constructed(tags) => {
+                    write_type(out, tag.class, TagStructure::Constructed, tag.id)?;
+                    match rules {
+                        Rules::Der => write_length(out, lengths.next().unwrap_or(0))?,
+                        _ => out.write_all(&[INDEFINITE_LENGTH])?,
+                    }
+                    let set = tag.class == TagClass::Universal && tag.id == universal::Types::Set as u64;
+                    stack.push(CanonicalFrame {
+                        class: tag.class,
+                        id: tag.id,
+                        children: tags.iter(),
+                        members: if set { Some(Vec::with_capacity(tags.len())) } else { None },
+                        member,
+                    });
+                },
+            }
+        }
+
+        next = match stack.last_mut() {
+            Some(top) => top.children.next(),
+            None => return Ok(()),
+        };
+        if next.is_none() {
+            let frame = stack.pop().unwrap();
+            let out: &mut dyn Write = match buffers.last_mut() {
+                Some(buf) => buf,
+                None => &mut *w,
+            };
+            if let Some(mut members) = frame.members {
+                sort_set(&mut members);
+                for member in members {
+                    out.write_all(&member.2)?;
+                }
+            }
+            if rules == Rules::Cer {
+                out.write_all(&END_OF_CONTENTS)?;
+            }
+            if frame.member {
+                add_member(&mut stack, &mut buffers, frame.class, frame.id);
+            }
+        }
+    }
+}
 
+/// Hand the innermost buffer over to the SET on top of `stack` as a member with the given tag.
+fn add_member(stack: &mut [CanonicalFrame], buffers: &mut Vec<Vec<u8>>, class: TagClass, id: u64) {
+    let encoding = buffers.pop().unwrap_or_default();
+    if let Some(members) = stack.last_mut().and_then(|top| top.members.as_mut()) {
+        members.push((class, id, encoding));
+    }
+}
+
+/// Write a primitive tag in its canonical form. CER splits strings longer than 1000 octets into
+/// a constructed string of segments.
+fn write_canonical_primitive(w: &mut dyn Write, class: TagClass, id: u64, v: &[u8], rules: Rules) -> Result<(), Error> {
+    if rules == Rules::Cer && class == TagClass::Universal && universal::is_string_type(id) && v.len() > CER_SEGMENT_LEN {
+        write_type(w, class, TagStructure::Constructed, id)?;
+        w.write_all(&[INDEFINITE_LENGTH])?;
+        for segment in cer_segments(v, id == universal::Types::BitString as u64) {
+            write_definite(w, &segment, Vec::new())?;
+        }
+        w.write_all(&END_OF_CONTENTS)?;
+    } else {
+        write_type(w, class, TagStructure::Primitive, id)?;
+        write_length(w, v.len())?;
+        w.write_all(v)?;
+    }
     Ok(())
 }
 
-pub fn write_type(mut w: &mut Write, class: TagClass, structure: TagStructure, id: u64) {
+/// A member of a universal SET in the form DER and CER order them by: its class, id and encoding.
+pub(crate) type SetMember = (TagClass, u64, Vec<u8>);
+
+/// The order of the members of a SET: by tag, and members with the same tag by their encoding.
+pub(crate) fn set_order(a: &SetMember, b: &SetMember) -> Ordering {
+    (a.0 as u8, a.1, &a.2).cmp(&(b.0 as u8, b.1, &b.2))
+}
+
+/// The order of the members of a SET OF: by encoding alone.
+pub(crate) fn set_of_order(a: &SetMember, b: &SetMember) -> Ordering {
+    a.2.cmp(&b.2)
+}
+
+/// Checks that the members of a universal SET come in an order DER and CER allow.
+///
+/// SET and SET OF share their tag, so there is no telling which one an encoding holds, and members
+/// in either `set_order` or `set_of_order` are accepted.
+pub(crate) struct SetOrder {
+    by_tag: bool,
+    by_encoding: bool,
+}
+
+impl SetOrder {
+    pub(crate) fn new() -> SetOrder {
+        SetOrder { by_tag: true, by_encoding: true }
+    }
+
+    /// Take the next member `b` following `a`. Returns false once the members are in neither order.
+    pub(crate) fn follows(&mut self, a: &SetMember, b: &SetMember) -> bool {
+        self.by_tag &= set_order(a, b) != Ordering::Greater;
+        self.by_encoding &= set_of_order(a, b) != Ordering::Greater;
+        self.by_tag || self.by_encoding
+    }
+}
+
+/// Bring the encoded members of a universal SET into an order DER and CER allow.
+///
+/// Members already in one are left as they are, so a SET OF sorted by `ASNTag::into_der_structure`
+/// keeps its order. Anything else is sorted by `set_order`, as for a SET.
+fn sort_set(parts: &mut [SetMember]) {
+    let mut order = SetOrder::new();
+    if !parts.windows(2).all(|w| order.follows(&w[0], &w[1])) {
+        parts.sort_by(set_order);
+    }
+}
+
+/// The longest string content CER allows to be encoded primitively, and the size of every but
+/// the last segment otherwise.
+pub const CER_SEGMENT_LEN: usize = 1000;
+
+/// Split string content into the primitive segments of its CER encoding.
+///
+/// Segments of a BIT STRING are BIT STRINGs themselves and carry their own unused bits octet,
+/// segments of all other string types are OCTET STRINGs.
+pub(crate) fn cer_segments(v: &[u8], bitstring: bool) -> Vec<StructureTag> {
+    let segment = |payload: Vec<u8>| StructureTag {
+        class: TagClass::Universal,
+        id: if bitstring { universal::Types::BitString as u64 } else { universal::Types::OctetString as u64 },
+        payload: PL::P(payload),
+    };
+
+    if !bitstring {
+        return v.chunks(CER_SEGMENT_LEN).map(|c| segment(c.to_vec())).collect();
+    }
+
+    if v.len() < 2 {
+        return vec![segment(if v.is_empty() { vec![0] } else { v.to_vec() })];
+    }
+
+    let (unused, bits) = (v[0], &v[1..]);
+    let chunks: Vec<&[u8]> = bits.chunks(CER_SEGMENT_LEN - 1).collect();
+    let last = chunks.len() - 1;
+    chunks.into_iter().enumerate().map(|(n, c)| {
+        let mut payload = Vec::with_capacity(c.len() + 1);
+        payload.push(if n == last { unused } else { 0 });
+        payload.extend_from_slice(c);
+        segment(payload)
+    }).collect()
+}
+
+/// What gets encoded of a tag: the content of a primitive tag, or the children of a constructed
+/// one.
+enum Form<'a> {
+    Primitive(Cow<'a, [u8]>),
+    Constructed(&'a [StructureTag]),
+}
+
+impl<'a> Form<'a> {
+    /// The tag as it is.
+    fn of(tag: &'a StructureTag) -> Form<'a> {
+        match tag.payload {
+            PL::P(ref v) => Form::Primitive(Cow::Borrowed(v)),
+            PL::C(ref tags) => Form::Constructed(tags),
+        }
+    }
+
+    /// The tag in the single form DER allows for it, borrowing its content if it is in that form
+    /// already. Only universal tags are rewritten.
+    fn der(tag: &'a StructureTag) -> Form<'a> {
+        if tag.class != TagClass::Universal {
+            return Form::of(tag);
+        }
+
+        match tag.payload {
+            PL::P(ref v) => Form::Primitive(
+                if tag.id == universal::Types::Boolean as u64 && v.len() == 1 && v[0] != 0 && v[0] != 0xFF {
+                    Cow::Owned(vec![0xFF])
+                } else if (tag.id == universal::Types::Integer as u64 || tag.id == universal::Types::Enumerated as u64)
+                       && redundant_int_octets(v) > 0 {
+                    Cow::Borrowed(&v[redundant_int_octets(v)..])
+                } else if tag.id == universal::Types::BitString as u64 && v.len() > 1 && v[0] < 8
+                       && v[v.len() - 1] & !(0xFF << v[0]) != 0 {
+                    let mut v = v.clone();
+                    let last = v.len() - 1;
+                    v[last] &= 0xFF << v[0];
+                    Cow::Owned(v)
+                } else {
+                    Cow::Borrowed(v)
+                }
+            ),
+            PL::C(ref tags) if universal::is_string_type(tag.id) =>
+                Form::Primitive(Cow::Owned(flatten_segments(tags, tag.id == universal::Types::BitString as u64))),
+            PL::C(ref tags) => Form::Constructed(tags),
+        }
+    }
+}
+
+/// Number of leading octets of a two's complement integer that don't change its value.
+pub(crate) fn redundant_int_octets(v: &[u8]) -> usize {
+    let mut n = 0;
+    while n + 1 < v.len() {
+        match (v[n], v[n+1] & 0x80) {
+            (0x00, 0x00) | (0xFF, 0x80) => n += 1,
+            _ => break,
+        }
+    }
+    n
+}
+
+/// Join the segments of a constructed string into the content of a primitive one.
+///
+/// Every segment of a BIT STRING starts with its own unused bits octet, of which only the last one
+/// survives.
+fn flatten_segments(tags: &[StructureTag], bitstring: bool) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut unused = 0u8;
+    if bitstring {
+        out.push(0);
+    }
+
+    // Segments left to join of the constructed segments being joined.
+    let mut stack = vec![tags.iter()];
+    while let Some(top) = stack.last_mut() {
+        let tag = match top.next() {
+            Some(tag) => tag,
+            None => {
+                stack.pop();
+                continue;
+            },
+        };
+        match tag.payload {
+            PL::P(ref v) => {
+                if bitstring {
+                    if let Some((&first, rest)) = v.split_first() {
+                        unused = first;
+                        out.extend_from_slice(rest);
+                    }
+                } else {
+                    out.extend_from_slice(v);
+                }
+            },
+            PL::C(ref inner) => stack.push(inner.iter()),
+        }
+    }
+
+    if bitstring {
+        out[0] = unused;
+    }
+    out
+}
+
+/// Number of octets the definite length encoding of `tag` takes up.
+pub fn encoded_len(tag: &StructureTag) -> usize {
+    match tag.payload {
+        PL::P(ref v) => type_len(tag.id) + length_len(v.len()) + v.len(),
+        PL::C(_) => total_len(tag, &content_lengths(tag)),
+    }
+}
+
+/// Number of content octets of the definite length encoding of `tag`.
+pub(crate) fn content_len(tag: &StructureTag) -> usize {
+    match tag.payload {
+        PL::P(ref v) => v.len(),
+        PL::C(_) => content_lengths(tag)[0],
+    }
+}
+
+/// Number of octets the definite length encoding of `tag` takes up, given its `content_lengths`.
+fn total_len(tag: &StructureTag, lengths: &[usize]) -> usize {
+    let content = match tag.payload {
+        PL::P(ref v) => v.len(),
+        PL::C(_) => lengths[0],
+    };
+    type_len(tag.id) + length_len(content) + content
+}
+
+/// Number of octets the identifier octets for tag number `id` take up.
+pub(crate) fn type_len(id: u64) -> usize {
+    let mut count = 1;
+    let mut tag = id;
+    if id > 30 {
+        while tag > 0 {
+            count += 1;
+            tag >>= 7;
+        }
+    }
+    count
+}
+
+/// Number of octets the definite length octets for `length` take up.
+pub(crate) fn length_len(length: usize) -> usize {
+    let mut count = 1;
+    let mut len = length;
+    if length >= 128 {
+        while len > 0 {
+            count += 1;
+            len >>= 8;
+        }
+    }
+    count
+}
+
+/// Encode a tag using the indefinite length form for every constructed tag.
+///
+/// Constructed content is written straight to `w` and terminated by end-of-contents octets, so
+/// unlike `encode_into` no child is ever buffered to learn its length.
+pub fn encode_indefinite(w: &mut dyn Write, tag: StructureTag) -> Result<(), Error> {
+    write_indefinite(w, &tag)
+}
+
+fn write_indefinite(w: &mut dyn Write, tag: &StructureTag) -> Result<(), Error> {
+    // Children left to encode of the constructed tags being encoded.
+    let mut stack: Vec<slice::Iter<StructureTag>> = Vec::new();
+    let mut next = Some(tag);
+
+    loop {
+        if let Some(tag) = next.take() {
+            match tag.payload {
+                PL::P(ref v) => {
+                    write_type(w, tag.class, TagStructure::Primitive, tag.id)?;
+                    write_length(w, v.len())?;
+                    w.write_all(v)?;
+                },
+                PL::C(ref tags) => {
+                    write_type(w, tag.class, TagStructure::Constructed, tag.id)?;
+                    w.write_all(&[INDEFINITE_LENGTH])?;
+                    stack.push(tags.iter());
+                },
+            }
+        }
+
+        next = match stack.last_mut() {
+            Some(top) => top.next(),
+            None => return Ok(()),
+        };
+        if next.is_none() {
+            stack.pop();
+            w.write_all(&END_OF_CONTENTS)?;
+        }
+    }
+}
+
+const INDEFINITE_LENGTH: u8 = 0x80;
+const END_OF_CONTENTS: [u8; 2] = [0x00, 0x00];
+
+/// Incrementally write constructed tags with indefinite length.
+///
+/// This allows emitting a constructed tag whose children are produced on the fly, without ever
+/// holding the whole structure in memory.
+pub struct IndefiniteWriter<W: Write> {
+    inner: W,
+    depth: usize,
+}
+
+impl<W: Write> IndefiniteWriter<W> {
+    pub fn new(inner: W) -> IndefiniteWriter<W> {
+        IndefiniteWriter { inner, depth: 0 }
+    }
+
+    /// Open a constructed tag. Everything written until the matching `end_constructed` call
+    /// becomes its content.
+    pub fn start_constructed(&mut self, class: TagClass, id: u64) -> Result<(), Error> {
+        write_type(&mut self.inner, class, TagStructure::Constructed, id)?;
+        self.inner.write_all(&[INDEFINITE_LENGTH])?;
+        self.depth += 1;
+        Ok(())
+    }
+
+    /// Close the innermost open constructed tag.
+    pub fn end_constructed(&mut self) -> Result<(), Error> {
+        if self.depth == 0 {
+            return Err(Error::Unbalanced { open: 0 });
+        }
+        self.inner.write_all(&END_OF_CONTENTS)?;
+        self.depth -= 1;
+        Ok(())
+    }
+
+    /// Write a complete tag into the currently open constructed tag.
+    pub fn write_tag(&mut self, tag: StructureTag) -> Result<(), Error> {
+        encode_indefinite(&mut self.inner, tag)
+    }
+
+    /// Number of constructed tags that have been started but not yet ended.
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Flush and return the underlying writer. Fails if constructed tags are still open.
+    pub fn into_inner(mut self) -> Result<W, Error> {
+        if self.depth != 0 {
+            return Err(Error::Unbalanced { open: self.depth });
+        }
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+pub fn write_type(w: &mut dyn Write, class: TagClass, structure: TagStructure, id: u64) -> Result<(), Error> {
     let extended_tag: Option<Vec<u8>>;
 
     let type_byte = {
@@ -72,7 +628,7 @@ pub fn write_type(mut w: &mut Write, class: TagClass, structure: TagStructure, i
         }
     }; // let type_byte
 
-    let _ = w.write_u8(type_byte);
+    w.write_u8(type_byte)?;
 
     if let Some(mut ext_bytes) = extended_tag
     {
@@ -83,20 +639,22 @@ pub fn write_type(mut w: &mut Write, class: TagClass, structure: TagStructure, i
             // Set the first bit
             byte |= 0x80;
 
-            let _ = w.write_u8(byte);
+            w.write_u8(byte)?;
         }
 
         let byte = ext_bytes.pop().unwrap();
-        let _ = w.write_u8(byte);
+        w.write_u8(byte)?;
     }
+
+    Ok(())
 }
 
 // Yes I know you could overflow the length in theory. But, do you have 2^64 bytes of memory?
-pub fn write_length(mut w: &mut Write, length: usize) {
+pub fn write_length(w: &mut dyn Write, length: usize) -> Result<(), Error> {
     // Short form
     if length < 128
     {
-        let _ = w.write_u8(length as u8);
+        w.write_u8(length as u8)?;
     }
     // Long form
     else
@@ -106,23 +664,23 @@ pub fn write_length(mut w: &mut Write, length: usize) {
         while {count += 1; len >>= 8; len > 0 }{}
 
 
-        let _ = w.write_u8(count | 0x80);
-        let _ = w.write_uint::<BigEndian>(length as u64, count as usize);
+        w.write_u8(count | 0x80)?;
+        w.write_uint::<BigEndian>(length as u64, count as usize)?;
     }
+
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
-    use super::*;
-
-    use std::string;
-
     use std::default::Default;
 
-    use byteorder::{BigEndian, WriteBytesExt};
-
     use structures::*;
     use common::TagClass::*;
+    use parse::{parse_tag, parse_tag_cer};
+    use nom::IResult;
+    use structure::{StructureTag, PL};
+    use error::Error;
 
     #[test]
     fn encode_simple_tag() {
@@ -132,7 +690,7 @@ mod tests {
         });
 
         let mut buf = Vec::<u8>::new();
-        super::encode_into(&mut buf, tag.into_structure());
+        super::encode_into(&mut buf, tag.into_structure()).unwrap();
 
         assert_eq!(buf, vec![0x2, 0x2, 0x06, 0x50]);
     }
@@ -151,11 +709,35 @@ mod tests {
         });
 
         let mut buf = Vec::<u8>::new();
-        super::encode_into(&mut buf, tag.into_structure());
+        super::encode_into(&mut buf, tag.into_structure()).unwrap();
 
         assert_eq!(buf, vec![48,14,4,12,72,101,108,108,111,32,87,111,114,108,100,33]);
     }
 
+    #[test]
+    fn encode_definite_precomputed_lengths()
+    {
+        // A SEQUENCE whose content needs the long length form, around one that doesn't.
+        let tag = StructureTag { class: Universal, id: 16, payload: PL::C(vec![
+            StructureTag { class: Context, id: 31, payload: PL::P(vec![0x61; 200]) },
+            StructureTag { class: Universal, id: 16, payload: PL::C(vec![
+                StructureTag { class: Universal, id: 5, payload: PL::P(vec![]) },
+            ])},
+        ])};
+        assert_eq!(tag.encoded_len(), 3 + (4 + 200) + (2 + 2));
+        assert_eq!(super::encoded_len(&tag.clone().expect_constructed().unwrap()[1]), 4);
+
+        let mut buf = Vec::new();
+        super::encode_into(&mut buf, tag.clone()).unwrap();
+        assert_eq!(buf.len(), tag.encoded_len());
+        assert_eq!(&buf[..7], &[0x30, 0x81, 0xD0, 0x9F, 0x1F, 0x81, 0xC8]);
+        assert_eq!(&buf[buf.len() - 4..], &[0x30, 0x02, 0x05, 0x00]);
+
+        let mut out = ::std::io::Cursor::new(Vec::new());
+        super::encode_definite(&mut out, tag).unwrap();
+        assert_eq!(out.into_inner(), buf);
+    }
+
     #[test]
     fn complex_tag()
     {
@@ -198,8 +780,255 @@ mod tests {
         ];
 
         let mut buf = Vec::<u8>::new();
-        super::encode_into(&mut buf, tag.into_structure());
+        super::encode_into(&mut buf, tag.into_structure()).unwrap();
+
+        assert_eq!(buf, expected);
+    }
+
+    #[test]
+    fn encode_indefinite_tag()
+    {
+        let tag = Tag::Sequence(Sequence {
+            inner: vec![
+                Tag::Integer(Integer {
+                    inner: 1,
+                    .. Default::default()
+                }),
+                Tag::Sequence(Sequence {
+                    id: 0,
+                    class: Application,
+                    inner: vec![
+                        Tag::OctetString(OctetString {
+                            inner: String::from("asdf").into_bytes(),
+                            .. Default::default()
+                        })
+                    ]
+                })
+            ],
+            .. Default::default()
+        });
+
+        let expected = vec![
+            0x30, 0x80,
+                0x02, 0x01, 0x01,
+                0x60, 0x80,
+                    0x04, 0x04, 0x61, 0x73, 0x64, 0x66,
+                0x00, 0x00,
+            0x00, 0x00
+        ];
+
+        let mut buf = Vec::<u8>::new();
+        super::encode_indefinite(&mut buf, tag.clone().into_structure()).unwrap();
+
+        assert_eq!(buf, expected);
+        assert_eq!(parse_tag(&buf), IResult::Done(&[][..], tag.into_structure()));
+    }
+
+    #[test]
+    fn indefinite_writer()
+    {
+        let mut writer = super::IndefiniteWriter::new(Vec::<u8>::new());
+        writer.start_constructed(Universal, 16).unwrap();
+        for i in 0..3 {
+            writer.write_tag(Integer { inner: i, .. Default::default() }.into_structure()).unwrap();
+        }
+        writer.end_constructed().unwrap();
+        assert_eq!(writer.depth(), 0);
+        assert_eq!(writer.end_constructed().err(), Some(Error::Unbalanced { open: 0 }));
+
+        let buf = writer.into_inner().unwrap();
+        assert_eq!(buf, vec![0x30, 0x80, 0x02, 0x01, 0x00, 0x02, 0x01, 0x01, 0x02, 0x01, 0x02, 0x00, 0x00]);
+
+        let mut writer = super::IndefiniteWriter::new(Vec::<u8>::new());
+        writer.start_constructed(Universal, 16).unwrap();
+        assert_eq!(writer.into_inner().err(), Some(Error::Unbalanced { open: 1 }));
+    }
+
+    #[test]
+    fn encode_der_canonical()
+    {
+        let tag = StructureTag {
+            class: Universal,
+            id: 17,
+            payload: PL::C(vec![
+                StructureTag { class: Universal, id: 4, payload: PL::C(vec![
+                    StructureTag { class: Universal, id: 4, payload: PL::P(vec![0x61, 0x62]) },
+                    StructureTag { class: Universal, id: 4, payload: PL::P(vec![0x63, 0x64]) },
+                ])},
+                StructureTag { class: Universal, id: 2, payload: PL::P(vec![0x00, 0x05]) },
+                StructureTag { class: Universal, id: 1, payload: PL::P(vec![0x01]) },
+            ]),
+        };
+
+        let expected = vec![
+            0x31, 0x0C,
+                0x01, 0x01, 0xFF,
+                0x02, 0x01, 0x05,
+                0x04, 0x04, 0x61, 0x62, 0x63, 0x64
+        ];
+
+        let mut buf = Vec::<u8>::new();
+        super::encode_der_into(&mut buf, tag).unwrap();
 
         assert_eq!(buf, expected);
     }
+
+    #[test]
+    fn encode_der_bitstring()
+    {
+        let tag = StructureTag {
+            class: Universal,
+            id: 3,
+            payload: PL::C(vec![
+                StructureTag { class: Universal, id: 3, payload: PL::P(vec![0x00, 0xAA]) },
+                StructureTag { class: Universal, id: 3, payload: PL::P(vec![0x04, 0xB0]) },
+            ]),
+        };
+
+        let mut buf = Vec::<u8>::new();
+        super::encode_der_into(&mut buf, tag).unwrap();
+
+        assert_eq!(buf, vec![0x03, 0x03, 0x04, 0xAA, 0xB0]);
+
+        let tag = StructureTag { class: Universal, id: 3, payload: PL::P(vec![0x04, 0xAA, 0xBF]) };
+        let mut buf = Vec::<u8>::new();
+        super::encode_der_into(&mut buf, tag).unwrap();
+
+        assert_eq!(buf, vec![0x03, 0x03, 0x04, 0xAA, 0xB0]);
+    }
+
+    #[test]
+    fn encode_der_implicit_set_of()
+    {
+        let tag = SetOf {
+            id: 1,
+            class: Context,
+            inner: vec![
+                OctetString { inner: vec![0x62], .. Default::default() },
+                OctetString { inner: vec![0x61], .. Default::default() },
+            ],
+        };
+
+        let mut buf = Vec::<u8>::new();
+        super::encode_der_into(&mut buf, tag.into_der_structure()).unwrap();
+
+        assert_eq!(buf, vec![0xA1, 0x06, 0x04, 0x01, 0x61, 0x04, 0x01, 0x62]);
+    }
+
+    #[test]
+    fn encode_der_set_order()
+    {
+        // In neither order, so sorted by tag.
+        let tag = StructureTag {
+            class: Universal,
+            id: 17,
+            payload: PL::C(vec![
+                StructureTag { class: Context, id: 2, payload: PL::C(vec![]) },
+                StructureTag { class: Context, id: 1, payload: PL::P(vec![0x00]) },
+            ]),
+        };
+
+        let mut buf = Vec::<u8>::new();
+        super::encode_der_into(&mut buf, tag).unwrap();
+
+        assert_eq!(buf, vec![0x31, 0x05, 0x81, 0x01, 0x00, 0xA2, 0x00]);
+        assert_eq!(::parse::validate_der(&::parse::parse_tag(&buf[..]).unwrap().1), Ok(()));
+
+        // Sorted by encoding, as for a SET OF, so left alone.
+        let tag = StructureTag {
+            class: Universal,
+            id: 17,
+            payload: PL::C(vec![
+                StructureTag { class: Context, id: 2, payload: PL::P(vec![0x00]) },
+                StructureTag { class: Context, id: 1, payload: PL::C(vec![]) },
+            ]),
+        };
+
+        let mut buf = Vec::<u8>::new();
+        super::encode_der_into(&mut buf, tag).unwrap();
+
+        assert_eq!(buf, vec![0x31, 0x05, 0x82, 0x01, 0x00, 0xA1, 0x00]);
+        assert_eq!(::parse::validate_der(&::parse::parse_tag(&buf[..]).unwrap().1), Ok(()));
+    }
+
+    #[test]
+    fn encode_der_set_of_choice()
+    {
+        let members = vec![
+            Tag::StructureTag(StructureTag { class: Context, id: 1, payload: PL::C(vec![]) }),
+            Tag::StructureTag(StructureTag { class: Context, id: 2, payload: PL::P(vec![0x00]) }),
+        ];
+
+        let set_of = SetOf { inner: members.clone(), .. Default::default() };
+        let mut buf = Vec::<u8>::new();
+        super::encode_der_into(&mut buf, set_of.into_der_structure()).unwrap();
+        assert_eq!(buf, vec![0x31, 0x05, 0x82, 0x01, 0x00, 0xA1, 0x00]);
+
+        let set = Set { inner: members.into_iter().rev().collect(), .. Default::default() };
+        let mut buf = Vec::<u8>::new();
+        super::encode_der_into(&mut buf, set.into_der_structure()).unwrap();
+        assert_eq!(buf, vec![0x31, 0x05, 0xA1, 0x00, 0x82, 0x01, 0x00]);
+    }
+
+    #[test]
+    fn encode_cer_sequence()
+    {
+        let tag = Tag::Sequence(Sequence {
+            inner: vec![
+                Tag::Integer(Integer { inner: 1, .. Default::default() }),
+                Tag::Boolean(Boolean { inner: true, .. Default::default() }),
+            ],
+            .. Default::default()
+        });
+
+        let mut buf = Vec::<u8>::new();
+        super::encode_cer(&mut buf, tag.into_cer_structure()).unwrap();
+
+        assert_eq!(buf, vec![0x30, 0x80, 0x02, 0x01, 0x01, 0x01, 0x01, 0xFF, 0x00, 0x00]);
+        assert!(parse_tag_cer(&buf).is_done());
+    }
+
+    #[test]
+    fn encode_cer_segments()
+    {
+        let tag = OctetString { inner: vec![0x61; 2500], .. Default::default() };
+
+        let mut buf = Vec::<u8>::new();
+        super::encode_cer(&mut buf, tag.into_structure()).unwrap();
+
+        assert_eq!(&buf[..6], &[0x24, 0x80, 0x04, 0x82, 0x03, 0xE8][..]);
+        assert_eq!(&buf[1006..1010], &[0x04, 0x82, 0x03, 0xE8][..]);
+        assert_eq!(&buf[2010..2014], &[0x04, 0x82, 0x01, 0xF4][..]);
+        assert_eq!(&buf[2514..], &[0x00, 0x00][..]);
+
+        let parsed = parse_tag_cer(&buf).unwrap().1;
+        let segments = parsed.expect_constructed().unwrap();
+        assert_eq!(segments.len(), 3);
+
+        // Implicitly tagged strings are segmented as well, into universal OCTET STRINGs.
+        let tag = OctetString { id: 0, class: Context, inner: vec![0x61; 1500] };
+
+        let mut buf = Vec::<u8>::new();
+        super::encode_cer(&mut buf, tag.into_cer_structure()).unwrap();
+
+        assert_eq!(&buf[..6], &[0xA0, 0x80, 0x04, 0x82, 0x03, 0xE8][..]);
+        assert!(parse_tag_cer(&buf).is_done());
+    }
+
+    #[test]
+    fn encode_cer_bitstring_segments()
+    {
+        let mut content = vec![0x03];
+        content.extend(vec![0xFF; 1500]);
+        content.push(0xF8);
+        let tag = StructureTag { class: Universal, id: 3, payload: PL::P(content) };
+
+        let mut buf = Vec::<u8>::new();
+        super::encode_cer(&mut buf, tag).unwrap();
+
+        // 999 data octets per segment, only the last one carrying unused bits.
+        assert_eq!(&buf[..7], &[0x23, 0x80, 0x03, 0x82, 0x03, 0xE8, 0x00][..]);
+        assert_eq!(&buf[1006..1011], &[0x03, 0x82, 0x01, 0xF7, 0x03][..]);
+        assert!(parse_tag_cer(&buf).is_done());
+    }
 }